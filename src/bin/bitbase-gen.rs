@@ -0,0 +1,41 @@
+// Generates win/draw/loss bitbases for endings with few pieces, by retrograde analysis.
+// Usage: bitbase-gen DIRECTORY [SIGNATURE...]
+// A SIGNATURE names the pieces of both sides, such as KRKP. Without signatures the common 3- and
+// 4-man endings are generated. The endings they turn into are generated as well, and tables already
+// in DIRECTORY are kept. Set STOCKWISH_BITBASES to the directory to use them.
+use std::env;
+use std::path::Path;
+
+use stockwish::stockwishbot::Bitbases;
+
+const DEFAULT_ENDINGS: [&str; 10] = [
+    "KPK", "KRK", "KQK", "KBNK", "KRKP", "KQKP", "KQKR", "KRKB", "KRKN", "KPKP",
+];
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("Usage: bitbase-gen DIRECTORY [SIGNATURE...]");
+    }
+    let directory = &args[1];
+    let mut bitbases = if Path::new(directory).is_dir() {
+        Bitbases::load(directory)?
+    } else {
+        Bitbases::default()
+    };
+    let signatures: Vec<&str> = if args.len() > 2 {
+        args[2..].iter().map(String::as_str).collect()
+    } else {
+        DEFAULT_ENDINGS.to_vec()
+    };
+    for signature in signatures {
+        bitbases.generate(signature)?;
+    }
+    bitbases.save(directory)?;
+    println!(
+        "Wrote the {} bitbases to {}",
+        bitbases.signatures().join(", "),
+        directory
+    );
+    Ok(())
+}
@@ -0,0 +1,428 @@
+// Builds a Polyglot opening book from PGN files, ready for STOCKWISH_BOOK.
+//
+// Usage: book-builder OUTPUT PGN... [OPTIONS]
+//   --max-ply N       Only the first N plies of every game are counted (default 20)
+//   --min-elo N       Only games in which both players are rated at least N
+//   --result R        Only games with this result (1-0, 0-1 or 1/2-1/2). May be repeated.
+//   --min-games N     Only moves played in at least N games (default 3)
+//   --weighting W     "games" weighs a move by how often it was played, "win-rate" by the share
+//                     of the points it scored for the player making it, however rarely it was
+//                     played, so --min-games matters more with it (default win-rate)
+// Games which start from a set-up position are skipped.
+use chess::Board;
+use chess::ChessMove;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+
+use stockwish::stockwishbot::encode_move;
+use stockwish::stockwishbot::polyglot_key;
+use stockwish::stockwishbot::BookEntry;
+use stockwish::stockwishbot::OpeningBook;
+
+const USAGE: &str = "Usage: book-builder OUTPUT PGN... [--max-ply N] [--min-elo N] [--result R] [--min-games N] [--weighting games|win-rate]";
+const RESULTS: [&str; 3] = ["1-0", "0-1", "1/2-1/2"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Weighting {
+    Games,
+    WinRate,
+}
+
+struct Options {
+    output: String,
+    pgn_files: Vec<String>,
+    max_ply: usize,
+    min_elo: Option<i32>,
+    results: Vec<String>,
+    min_games: u32,
+    weighting: Weighting,
+}
+
+// How a move did, from the point-of-view of the player making it
+#[derive(Clone, Copy, Default)]
+struct MoveStats {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl MoveStats {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    fn weight(&self, weighting: Weighting) -> u64 {
+        match weighting {
+            Weighting::Games => self.games() as u64,
+            // The points per game, with a draw as half a point, scaled to the Polyglot weights
+            Weighting::WinRate => {
+                let half_points = 2 * self.wins as u64 + self.draws as u64;
+                half_points * u16::MAX as u64 / (2 * self.games() as u64)
+            }
+        }
+    }
+}
+
+// The statistics of every move, by the Polyglot key of the position and the Polyglot move
+type Statistics = HashMap<(u64, u16), MoveStats>;
+
+#[derive(Default)]
+struct Game {
+    headers: HashMap<String, String>,
+    movetext: String,
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = parse_options(env::args().skip(1).collect())?;
+    let mut statistics = Statistics::new();
+    let mut counted = 0;
+    let mut skipped = 0;
+    for path in &options.pgn_files {
+        read_games(path, |game| {
+            if add_game(&game, &options, &mut statistics) {
+                counted += 1;
+            } else {
+                skipped += 1;
+            }
+        })?;
+    }
+    println!("Counted {} games, skipped {}", counted, skipped);
+    let book = OpeningBook::from_entries(book_entries(&statistics, &options));
+    book.save(&options.output)?;
+    println!("Wrote {} book moves to {}", book.len(), options.output);
+    Ok(())
+}
+
+fn parse_options(args: Vec<String>) -> anyhow::Result<Options> {
+    let mut options = Options {
+        output: String::new(),
+        pgn_files: vec![],
+        max_ply: 20,
+        min_elo: None,
+        results: vec![],
+        min_games: 3,
+        weighting: Weighting::WinRate,
+    };
+    let mut files = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            files.push(arg);
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{} needs a value\n{}", arg, USAGE))?;
+        match arg.as_str() {
+            "--max-ply" => options.max_ply = value.parse()?,
+            "--min-elo" => options.min_elo = Some(value.parse()?),
+            "--result" if RESULTS.contains(&value.as_str()) => options.results.push(value),
+            "--min-games" => options.min_games = value.parse()?,
+            "--weighting" if value == "games" => options.weighting = Weighting::Games,
+            "--weighting" if value == "win-rate" => options.weighting = Weighting::WinRate,
+            _ => anyhow::bail!("Invalid option {} {}\n{}", arg, value, USAGE),
+        }
+    }
+    if files.len() < 2 {
+        anyhow::bail!(USAGE);
+    }
+    options.output = files.remove(0);
+    options.pgn_files = files;
+    if options.results.is_empty() {
+        options.results = RESULTS.iter().map(|result| result.to_string()).collect();
+    }
+    Ok(options)
+}
+
+//
+// Reading PGN
+//
+
+// Calls on_game for every game in the file, without holding more than one game in memory
+fn read_games(path: &str, mut on_game: impl FnMut(Game)) -> anyhow::Result<()> {
+    let mut game = Game::default();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.starts_with('%') {
+            // An escaped line, meant for other programs
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            // The headers of the next game
+            if !game.movetext.is_empty() {
+                on_game(std::mem::take(&mut game));
+            }
+            if let Some((name, value)) = parse_header(header) {
+                game.headers.insert(name, value);
+            }
+            continue;
+        }
+        // A semicolon comments out the rest of the line
+        let moves = line.split(';').next().unwrap_or_default();
+        game.movetext.push_str(moves);
+        game.movetext.push(' ');
+    }
+    if !game.movetext.trim().is_empty() {
+        on_game(game);
+    }
+    Ok(())
+}
+
+// A header such as [WhiteElo "2100"], without its opening bracket
+fn parse_header(header: &str) -> Option<(String, String)> {
+    let (name, rest) = header.split_once(' ')?;
+    let value = rest.trim().strip_suffix(']')?.trim().trim_matches('"');
+    Some((name.to_string(), value.to_string()))
+}
+
+// The moves of the main line, without comments, variations, annotations and move numbers
+fn san_moves(movetext: &str) -> Vec<String> {
+    let mut main_line = String::new();
+    let mut in_comment = false;
+    let mut variation_depth = 0;
+    for c in movetext.chars() {
+        match c {
+            '{' => in_comment = true,
+            '}' => in_comment = false,
+            _ if in_comment => {}
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => {}
+            _ => main_line.push(c),
+        }
+    }
+    main_line
+        .split_whitespace()
+        .filter(|token| !RESULTS.contains(token) && *token != "*" && !token.starts_with('$'))
+        .map(|token| {
+            // Move numbers may be written against the move, as in "1.e4"
+            token
+                .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+                .trim_end_matches(['+', '#', '!', '?'])
+        })
+        .filter(|token| !token.is_empty())
+        // The chess crate reads promotions without the equals sign, as in "e8Q"
+        .map(|token| token.replace('=', ""))
+        .collect()
+}
+
+//
+// Counting moves
+//
+
+// Adds the moves of the game to the statistics, if it passes the filters
+fn add_game(game: &Game, options: &Options, statistics: &mut Statistics) -> bool {
+    let Some(result) = game.headers.get("Result") else {
+        return false;
+    };
+    if !options.results.contains(result) || game.headers.contains_key("FEN") {
+        return false;
+    }
+    if let Some(min_elo) = options.min_elo {
+        let rated = ["WhiteElo", "BlackElo"].iter().all(|header| {
+            game.headers
+                .get(*header)
+                .and_then(|elo| elo.parse::<i32>().ok())
+                .is_some_and(|elo| elo >= min_elo)
+        });
+        if !rated {
+            return false;
+        }
+    }
+    // From white's point-of-view
+    let white_points = match result.as_str() {
+        "1-0" => 2,
+        "0-1" => 0,
+        _ => 1,
+    };
+    let mut board = Board::default();
+    for san in san_moves(&game.movetext).iter().take(options.max_ply) {
+        let Ok(chess_move) = ChessMove::from_san(&board, san) else {
+            // The rest of the game cannot be followed
+            break;
+        };
+        let points = match board.side_to_move() {
+            chess::Color::White => white_points,
+            chess::Color::Black => 2 - white_points,
+        };
+        let stats = statistics
+            .entry((polyglot_key(&board), encode_move(&board, chess_move)))
+            .or_default();
+        match points {
+            2 => stats.wins += 1,
+            1 => stats.draws += 1,
+            _ => stats.losses += 1,
+        }
+        board = board.make_move_new(chess_move);
+    }
+    true
+}
+
+// The book entries of all moves played often enough, best first within every position. The
+// weights of a position are scaled down together if the best does not fit in a Polyglot weight.
+fn book_entries(statistics: &Statistics, options: &Options) -> Vec<BookEntry> {
+    let mut positions: HashMap<u64, Vec<(u16, u64)>> = HashMap::new();
+    for (&(key, raw_move), stats) in statistics {
+        let weight = stats.weight(options.weighting);
+        if stats.games() >= options.min_games && weight > 0 {
+            positions.entry(key).or_default().push((raw_move, weight));
+        }
+    }
+    let mut entries = vec![];
+    for (key, mut moves) in positions {
+        moves.sort_by_key(|&(_, weight)| Reverse(weight));
+        let best = moves[0].1;
+        let scale = |weight: u64| {
+            let scaled = weight * u16::MAX as u64 / best.max(u16::MAX as u64);
+            scaled.max(1) as u16
+        };
+        entries.extend(moves.into_iter().map(|(raw_move, weight)| BookEntry {
+            key,
+            raw_move,
+            weight: scale(weight),
+            learn: 0,
+        }));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use stockwish::stockwishbot::decode_move;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/openings.pgn");
+
+    fn fixture_games() -> Vec<Game> {
+        let mut games = vec![];
+        read_games(FIXTURE, |game| games.push(game)).unwrap();
+        games
+    }
+
+    fn options(args: &[&str]) -> Options {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args.splice(0..0, ["book.bin".to_string(), FIXTURE.to_string()]);
+        parse_options(args).unwrap()
+    }
+
+    // The statistics of the fixture, and which of its games passed the filters
+    fn statistics(options: &Options) -> (Statistics, Vec<bool>) {
+        let mut statistics = Statistics::new();
+        let counted = fixture_games()
+            .iter()
+            .map(|game| add_game(game, options, &mut statistics))
+            .collect();
+        (statistics, counted)
+    }
+
+    fn board_after(moves: &[&str]) -> Board {
+        moves.iter().fold(Board::default(), |board, san| {
+            board.make_move_new(ChessMove::from_san(&board, san).unwrap())
+        })
+    }
+
+    // The moves and weights of the position, in the order of the book
+    fn book_moves(entries: &[BookEntry], board: &Board) -> Vec<(String, u16)> {
+        let key = polyglot_key(board);
+        entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .map(|entry| {
+                let chess_move = decode_move(board, entry.raw_move).unwrap();
+                (chess_move.to_string(), entry.weight)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn san_moves_skip_comments_variations_and_annotations() {
+        let games = fixture_games();
+        assert_eq!(games.len(), 5);
+        assert_eq!(games[0].headers["WhiteElo"], "2200");
+        assert_eq!(
+            san_moves(&games[0].movetext),
+            ["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O", "Bc5", "d3", "d6"]
+        );
+        assert_eq!(
+            san_moves(&games[1].movetext),
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]
+        );
+    }
+
+    #[test]
+    fn games_are_filtered() {
+        // The rating filter drops the third game, and set-up positions are always skipped
+        let (_, counted) = statistics(&options(&["--min-elo", "2000"]));
+        assert_eq!(counted, [true, true, false, false, true]);
+        let (_, counted) = statistics(&options(&["--result", "1-0", "--result", "0-1"]));
+        assert_eq!(counted, [true, false, true, false, true]);
+        let (statistics, _) = statistics(&options(&["--max-ply", "1", "--result", "1-0"]));
+        assert_eq!(statistics.len(), 1);
+    }
+
+    #[test]
+    fn moves_are_weighted_and_sorted() {
+        let after_e4 = board_after(&["e4"]);
+        let options = options(&["--min-elo", "2000", "--min-games", "1"]);
+        let (statistics, _) = statistics(&options);
+        let entries = book_entries(&statistics, &options);
+        // 1. e4 scored a win, a draw and a loss
+        assert_eq!(
+            book_moves(&entries, &Board::default()),
+            [("e2e4".to_string(), u16::MAX / 2)]
+        );
+        // For black, 1... c5 won its only game and 1... e5 scored a draw in two
+        assert_eq!(
+            book_moves(&entries, &after_e4),
+            [
+                ("c7c5".to_string(), u16::MAX),
+                ("e7e5".to_string(), u16::MAX / 4)
+            ]
+        );
+
+        let options = Options {
+            weighting: Weighting::Games,
+            ..options
+        };
+        let entries = book_entries(&statistics, &options);
+        assert_eq!(
+            book_moves(&entries, &after_e4),
+            [("e7e5".to_string(), 2), ("c7c5".to_string(), 1)]
+        );
+        let book = OpeningBook::from_entries(entries);
+        let keys: Vec<u64> = book
+            .to_bytes()
+            .chunks(16)
+            .map(|entry| u64::from_be_bytes(entry[0..8].try_into().unwrap()))
+            .collect();
+        assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn encoded_moves_decode_to_themselves() {
+        let mut board = Board::default();
+        for san in san_moves(&fixture_games()[0].movetext) {
+            let chess_move = ChessMove::from_san(&board, &san).unwrap();
+            assert_eq!(
+                decode_move(&board, encode_move(&board, chess_move)),
+                Some(chess_move)
+            );
+            board = board.make_move_new(chess_move);
+        }
+        let board = Board::from_str("1r5k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        for san in san_moves("1. a8=Q+ a8=N axb8=R+ axb8=B") {
+            let chess_move = ChessMove::from_san(&board, &san).unwrap();
+            assert!(chess_move.get_promotion().is_some());
+            assert_eq!(
+                decode_move(&board, encode_move(&board, chess_move)),
+                Some(chess_move)
+            );
+        }
+    }
+}
@@ -0,0 +1,29 @@
+// Prints how the evaluation arrives at its score for a position, given as a FEN.
+// Usage: eval-trace [FEN]. Without a FEN the starting position is used.
+// Set STOCKWISH_WEIGHTS to a JSON file to trace other evaluation weights.
+use chess::Board;
+use std::env;
+use std::str::FromStr;
+
+use stockwish::stockwishbot::evaluate_with_trace;
+use stockwish::stockwishbot::Calibration;
+use stockwish::stockwishbot::EvalWeights;
+
+fn main() {
+    // The FEN may be given as one argument or as separate words
+    let fen = env::args().skip(1).collect::<Vec<String>>().join(" ");
+    let board = if fen.is_empty() {
+        Board::default()
+    } else {
+        Board::from_str(&fen).expect("Invalid FEN")
+    };
+    let calibration = match env::var("STOCKWISH_WEIGHTS") {
+        Ok(path) => Calibration {
+            weights: EvalWeights::load(path).expect("Invalid evaluation weights"),
+            ..Calibration::default()
+        },
+        Err(_) => Calibration::default(),
+    };
+    println!("{}", board);
+    print!("{}", evaluate_with_trace(&board, &calibration));
+}
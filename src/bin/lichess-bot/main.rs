@@ -10,10 +10,21 @@ use licoricedev::models::board::{Challengee, Event};
 use licoricedev::models::game::Player;
 use licoricedev::models::user::{LightUser, PerfType};
 use serde_json::to_string_pretty;
+use std::sync::Arc;
 use std::time::Duration;
 use std::{env, thread, time};
 
+use stockwish::stockwishbot::Bitbases;
+use stockwish::stockwishbot::BookOptions;
+use stockwish::stockwishbot::Calibration;
+use stockwish::stockwishbot::EvalWeights;
+use stockwish::stockwishbot::EvaluationMode;
+use stockwish::stockwishbot::Network;
+use stockwish::stockwishbot::OpeningBook;
+use stockwish::stockwishbot::OpeningVariety;
+use stockwish::stockwishbot::PonderHandle;
 use stockwish::stockwishbot::StockWish;
+use stockwish::stockwishbot::Tablebase;
 
 #[tokio::main]
 async fn main() -> LichessResult<()> {
@@ -42,47 +53,118 @@ async fn main() -> LichessResult<()> {
     Ok(())
 }
 
+// The engine playing a single game. While the opponent thinks, it ponders on their expected reply.
+enum Engine {
+    Idle(StockWish),
+    Pondering(PonderHandle),
+}
+
+fn new_engine() -> StockWish {
+    // Each game gets its own opening seed, unless one is given to reproduce an earlier game.
+    let variety = match env::var("STOCKWISH_OPENING_SEED") {
+        Ok(seed) => OpeningVariety::with_seed(seed.parse().expect("Invalid opening seed")),
+        Err(_) => OpeningVariety::new_game(),
+    };
+    // Evaluation weights can be swapped out, to compare them against each other
+    let mut calibration = match env::var("STOCKWISH_WEIGHTS") {
+        Ok(path) => Calibration {
+            weights: EvalWeights::load(path).expect("Invalid evaluation weights"),
+            ..Calibration::default()
+        },
+        Err(_) => Calibration::default(),
+    };
+    // So can the whole classical evaluation, for a network
+    if let Ok(path) = env::var("STOCKWISH_NETWORK") {
+        let network = Network::load(path).expect("Invalid network");
+        calibration.evaluation = EvaluationMode::Network(Arc::new(network));
+    }
+    // Generated bitbases, for when there are no tablebases
+    if let Ok(directory) = env::var("STOCKWISH_BITBASES") {
+        let bitbases = Bitbases::load(directory).expect("Invalid bitbase directory");
+        calibration.bitbases = Some(Arc::new(bitbases));
+    }
+    let mut stockwish = StockWish::new(8, calibration);
+    stockwish.set_opening_variety(Some(variety));
+    if let Ok(path) = env::var("STOCKWISH_BOOK") {
+        let book = OpeningBook::load(path).expect("Invalid opening book");
+        stockwish.set_book(Some(Arc::new(book)), BookOptions::default());
+    }
+    // Syzygy tables are large, so they are only used if they are already on disk
+    if let Ok(directory) = env::var("STOCKWISH_SYZYGY") {
+        let tablebase = Tablebase::open(directory).expect("Invalid tablebase directory");
+        stockwish.set_tablebase(Some(Arc::new(tablebase)));
+    }
+    stockwish
+}
+
+fn stop_pondering(engine: &mut Option<Engine>) {
+    if let Some(Engine::Pondering(ponder)) = engine.take() {
+        ponder.ponder_miss();
+    }
+}
+
 async fn play_game(id: String) {
     let lichess = Lichess::new(env::var("LICHESS_PAT_0").unwrap());
     let mut stream = lichess.stream_bot_game_state(&id).await.unwrap();
     let mut myself: Option<chess::Color> = None;
+    let mut engine: Option<Engine> = None;
     loop {
-        let bs_result = stream.try_next().await;
-        if let Ok(Some(board_state)) = bs_result {
-            match board_state {
-                BoardState::GameFull(game_full) => {
-                    if let Challengee::LightUser(white) = game_full.white {
-                        println!("White username is {}", white.username);
-                        if white.username == "stockwishbot" {
-                            myself = Some(Color::White);
-                        }
-                    }
-                    if let Challengee::LightUser(black) = game_full.black {
-                        println!("Black username is {}", black.username);
-                        if black.username == "stockwishbot" {
-                            myself = Some(Color::Black);
-                        }
-                    }
-                    if myself.is_none() {
-                        panic!("Cannot figure out who I am?!");
-                    }
-                    if let Some(winner) = &game_full.state.winner {
-                        println!("Game over. Winner is {}", winner);
-                        break;
+        let board_state = match stream.try_next().await {
+            Ok(Some(board_state)) => board_state,
+            Ok(None) => {
+                println!("Game stream ended");
+                break;
+            }
+            Err(error) => {
+                println!("Game stream failed: {:?}", error);
+                break;
+            }
+        };
+        match board_state {
+            BoardState::GameFull(game_full) => {
+                if let Challengee::LightUser(white) = game_full.white {
+                    println!("White username is {}", white.username);
+                    if white.username == "stockwishbot" {
+                        myself = Some(Color::White);
                     }
-                    make_bot_move_if_own_turn(myself, game_full.state, &lichess, &id).await;
                 }
-                BoardState::GameState(game_state) => {
-                    if let Some(winner) = &game_state.winner {
-                        println!("Game over. Winner is {}", winner);
-                        break;
+                if let Challengee::LightUser(black) = game_full.black {
+                    println!("Black username is {}", black.username);
+                    if black.username == "stockwishbot" {
+                        myself = Some(Color::Black);
                     }
-                    make_bot_move_if_own_turn(myself, game_state, &lichess, &id).await;
                 }
-                _ => {}
+                if myself.is_none() {
+                    panic!("Cannot figure out who I am?!");
+                }
+                if is_game_over(&game_full.state) {
+                    break;
+                }
+                make_bot_move_if_own_turn(myself, game_full.state, &lichess, &id, &mut engine)
+                    .await;
             }
+            BoardState::GameState(game_state) => {
+                if is_game_over(&game_state) {
+                    break;
+                }
+                make_bot_move_if_own_turn(myself, game_state, &lichess, &id, &mut engine).await;
+            }
+            _ => {}
         }
     }
+    stop_pondering(&mut engine);
+}
+
+// Any status but these ends the game, whether or not there is a winner
+fn is_game_over(game_state: &GameState) -> bool {
+    if game_state.status == "created" || game_state.status == "started" {
+        return false;
+    }
+    match &game_state.winner {
+        Some(winner) => println!("Game over ({}). Winner is {}", game_state.status, winner),
+        None => println!("Game over ({}). No winner", game_state.status),
+    }
+    true
 }
 
 fn chess_game_from_lichess_state(game_state: GameState) -> chess::Game {
@@ -98,18 +180,52 @@ async fn make_bot_move_if_own_turn(
     game_state: GameState,
     lichess: &Lichess,
     id: &str,
+    engine: &mut Option<Engine>,
 ) {
     const MINIMUM_MOVE_TIME: Duration = Duration::from_millis(500);
+    // The search of every move stops after this long, whether or not it started as a ponder search
+    const MOVE_TIME: Duration = Duration::from_secs(2);
     if let Some(side) = myself {
+        let last_move = game_state
+            .moves
+            .split_ascii_whitespace()
+            .last()
+            .map(|m| m.parse::<ChessMove>().unwrap());
         let game = chess_game_from_lichess_state(game_state);
         if side == game.side_to_move() {
-            let mut stockwish = StockWish::default();
             let start = time::Instant::now();
-            let bot_move = stockwish.best_next_move_iterative_deepening(game);
+            let (mut stockwish, lines) = match engine.take() {
+                Some(Engine::Pondering(ponder)) if Some(ponder.ponder_move()) == last_move => {
+                    println!("Ponder hit!");
+                    ponder.ponder_hit(MOVE_TIME)
+                }
+                other => {
+                    let mut stockwish = match other {
+                        Some(Engine::Pondering(ponder)) => ponder.ponder_miss(),
+                        Some(Engine::Idle(stockwish)) => stockwish,
+                        None => new_engine(),
+                    };
+                    let lines = stockwish.analyse_timed(game.clone(), MOVE_TIME);
+                    (stockwish, lines)
+                }
+            };
             tokio::time::sleep_until((start + MINIMUM_MOVE_TIME).into()).await;
+            let best_line = stockwish
+                .pick_line(&lines)
+                .expect("No legal moves on our turn");
+            let bot_move = best_line.pv[0];
             let _ = lichess
-                .make_a_bot_move(id, &bot_move.unwrap().to_string(), false)
+                .make_a_bot_move(id, &bot_move.to_string(), false)
                 .await;
+            // Think about the expected reply while the opponent does the same
+            *engine = Some(match best_line.ponder_move() {
+                Some(ponder_move) => {
+                    let mut game = game;
+                    game.make_move(bot_move);
+                    Engine::Pondering(stockwish.ponder(game, ponder_move))
+                }
+                None => Engine::Idle(stockwish),
+            });
         }
     }
 }
@@ -17,15 +17,27 @@ use egui::Rounding;
 use egui::Sense;
 use egui::Shape;
 use egui::Vec2;
+use std::env;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::thread;
 // Thread communication
 use std::sync::mpsc::{channel, Receiver};
 
+use stockwish::stockwishbot::BookOptions;
+use stockwish::stockwishbot::OpeningBook;
+use stockwish::stockwishbot::Skill;
 use stockwish::stockwishbot::StockWish;
+use stockwish::stockwishbot::MAX_SKILL_LEVEL;
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+
+    // An optional skill level for the AI, from 0 up to MAX_SKILL_LEVEL (full strength).
+    let skill_level = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse::<u8>().ok())
+        .unwrap_or(MAX_SKILL_LEVEL);
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(800.0, 800.0)),
         ..Default::default()
@@ -33,7 +45,12 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "StockWish",
         options,
-        Box::new(|_cc| Box::<MyApp>::default()),
+        Box::new(move |_cc| {
+            Box::new(MyApp {
+                ai_controller: AIController::new(Skill::from_level(skill_level)),
+                ..Default::default()
+            })
+        }),
     )
 }
 
@@ -330,26 +347,52 @@ impl eframe::App for MyApp {
     }
 }
 
+// An engine which has finished its search, with the move it found
+type SearchResult = (StockWish, Option<ChessMove>);
+
 pub struct AIController {
     chess_ai_white: Option<StockWish>,
     chess_ai_black: Option<StockWish>,
-    receiver: Option<Receiver<Option<ChessMove>>>,
+    // The side whose engine is searching on another thread. The engine is sent back with its move,
+    // so that its random state carries over to the next move.
+    receiver: Option<(chess::Color, Receiver<SearchResult>)>,
+}
+
+// Plays from the opening book in STOCKWISH_BOOK, if there is one.
+fn new_stockwish() -> StockWish {
+    let mut stockwish = StockWish::default();
+    if let Ok(path) = env::var("STOCKWISH_BOOK") {
+        let book = OpeningBook::load(path).expect("Invalid opening book");
+        stockwish.set_book(Some(Arc::new(book)), BookOptions::default());
+    }
+    stockwish
 }
 
 impl Default for AIController {
     fn default() -> Self {
         Self {
             chess_ai_white: None,
-            chess_ai_black: Some(StockWish::default()),
+            chess_ai_black: Some(new_stockwish()),
             receiver: None,
         }
     }
 }
 
 impl AIController {
+    pub fn new(skill: Skill) -> Self {
+        let mut stockwish = new_stockwish();
+        stockwish.set_skill(skill);
+        Self {
+            chess_ai_white: None,
+            chess_ai_black: Some(stockwish),
+            receiver: None,
+        }
+    }
+
     pub fn disable(&mut self) {
         self.chess_ai_black = None;
         self.chess_ai_white = None;
+        self.receiver = None;
     }
 
     pub fn waiting_for_ai(&self) -> bool {
@@ -357,30 +400,36 @@ impl AIController {
     }
 
     pub fn controls(&self, c: chess::Color) -> bool {
+        let thinking = matches!(&self.receiver, Some((color, _)) if *color == c);
+        thinking
+            || match c {
+                chess::Color::Black => self.chess_ai_black.is_some(),
+                chess::Color::White => self.chess_ai_white.is_some(),
+            }
+    }
+
+    fn engine(&mut self, c: chess::Color) -> &mut Option<StockWish> {
         match c {
-            chess::Color::Black => self.chess_ai_black.is_some(),
-            chess::Color::White => self.chess_ai_white.is_some(),
+            chess::Color::Black => &mut self.chess_ai_black,
+            chess::Color::White => &mut self.chess_ai_white,
         }
     }
 
     pub fn schedule_move(&mut self, game: &Game) {
         // Schedules a move after a delay. This is idempotent, meaning that calling this recurringly does not result in several moves.
         if self.receiver.is_none() {
-            let (tx, rx) = channel::<Option<ChessMove>>();
-            self.receiver = Some(rx);
-            let ai = match game.side_to_move() {
-                chess::Color::Black => self.chess_ai_black.clone(),
-                chess::Color::White => self.chess_ai_white.clone(),
-            };
+            let side = game.side_to_move();
+            let mut ai = self
+                .engine(side)
+                .take()
+                .expect("No AI for the side to move");
+            let (tx, rx) = channel::<SearchResult>();
+            self.receiver = Some((side, rx));
             let game = game.clone();
-            assert!(ai.is_some());
             thread::spawn(move || {
-                let next_move = ai
-                    .clone()
-                    .unwrap()
-                    .best_next_move_iterative_deepening(game.clone());
-                tx.send(next_move)
-                    .expect("Error transmitting next move from AI");
+                let next_move = ai.best_next_move_iterative_deepening(game);
+                // Nobody is waiting for the move any more if the AI was disabled meanwhile
+                let _ = tx.send((ai, next_move));
             });
         }
     }
@@ -388,9 +437,11 @@ impl AIController {
     // TODO: How to distinguish between a null-result from the AI, and a null-result from the poll?
     pub fn poll_for_move(&mut self) -> Option<chess::ChessMove> {
         // If it is done, this is the AI's move.
-        if let Some(rx) = &self.receiver {
+        if let Some((side, rx)) = &self.receiver {
+            let side = *side;
             match rx.try_recv() {
-                Ok(chess_move) => {
+                Ok((ai, chess_move)) => {
+                    *self.engine(side) = Some(ai);
                     self.receiver = None;
                     return chess_move;
                 }
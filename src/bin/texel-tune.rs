@@ -0,0 +1,252 @@
+// Tunes the evaluation weights with Texel's method: the evaluation of quiet positions from real games
+// should predict the results of those games. The prediction error is minimised with a local search,
+// nudging every weight up and down one unit at a time.
+//
+// Usage: texel-tune POSITIONS OUTPUT [START_WEIGHTS] [MAX_PASSES]
+// POSITIONS has one position per line, as EPD with a c9 result opcode, or as a FEN followed by the
+// result ("1-0", "0-1", "1/2-1/2", or "[1.0]", "[0.5]", "[0.0]" from white's point-of-view).
+// The tuned weights are written to OUTPUT after every pass, ready for STOCKWISH_WEIGHTS.
+use chess::Board;
+use chess::BoardStatus;
+use chess::Color;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::str::FromStr;
+use std::thread;
+
+use stockwish::stockwishbot::quiet_position;
+use stockwish::stockwishbot::raw_board_score;
+use stockwish::stockwishbot::Calibration;
+use stockwish::stockwishbot::EvalWeights;
+use stockwish::stockwishbot::CENTIPAWN;
+
+// These only scale the other weights, so tuning them as well gains nothing.
+const FIXED_WEIGHTS: [&str; 2] = ["piece_value_scale", "positional_scale"];
+const DEFAULT_MAX_PASSES: usize = 100;
+
+struct Sample {
+    // Already resolved by the quiescence search
+    board: Board,
+    // 1 for a white win, 0.5 for a draw, 0 for a black win
+    result: f64,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        anyhow::bail!("Usage: texel-tune POSITIONS OUTPUT [START_WEIGHTS] [MAX_PASSES]");
+    }
+    let output = &args[2];
+    let weights = match args.get(3) {
+        Some(path) => EvalWeights::load(path)?,
+        None => EvalWeights::default(),
+    };
+    let max_passes = match args.get(4) {
+        Some(passes) => passes.parse()?,
+        None => DEFAULT_MAX_PASSES,
+    };
+    let calibration = Calibration {
+        weights,
+        ..Calibration::default()
+    };
+    let samples = read_samples(&args[1], &calibration)?;
+    println!("Read {} quiet positions", samples.len());
+    if samples.is_empty() {
+        anyhow::bail!("No usable positions in {}", args[1]);
+    }
+
+    let k = fit_scaling_constant(&samples, &calibration);
+    println!("Scaling constant K = {:.3}", k);
+    let tuned = local_search(&samples, calibration.weights, k, max_passes, output)?;
+    tuned.save(output)?;
+    println!("Wrote the tuned weights to {}", output);
+    Ok(())
+}
+
+//
+// Reading the positions
+//
+
+fn read_samples(path: &str, calibration: &Calibration) -> anyhow::Result<Vec<Sample>> {
+    let mut samples = vec![];
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((board, result)) = parse_line(line) else {
+            eprintln!("Skipping line {}: {}", number + 1, line);
+            continue;
+        };
+        // Decided positions say nothing about the weights
+        let board = quiet_position(&board, calibration);
+        if board.status() == BoardStatus::Ongoing {
+            samples.push(Sample { board, result });
+        }
+    }
+    Ok(samples)
+}
+
+fn parse_line(line: &str) -> Option<(Board, f64)> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 5 {
+        return None;
+    }
+    // EPD leaves out the move counters, which a FEN has
+    let counters = tokens[4..]
+        .iter()
+        .take(2)
+        .take_while(|token| token.parse::<u32>().is_ok())
+        .count();
+    let fen = match counters {
+        2 => tokens[..6].join(" "),
+        _ => format!("{} 0 1", tokens[..4].join(" ")),
+    };
+    let rest = tokens[4 + counters..].join(" ");
+    let result = if rest.contains("1/2-1/2") || rest.contains("[0.5]") {
+        0.5
+    } else if rest.contains("1-0") || rest.contains("[1.0]") {
+        1.0
+    } else if rest.contains("0-1") || rest.contains("[0.0]") {
+        0.0
+    } else {
+        return None;
+    };
+    Some((Board::from_str(&fen).ok()?, result))
+}
+
+//
+// The error of the evaluation
+//
+
+// The expected result for white, given an evaluation in centipawns
+fn sigmoid(k: f64, centipawns: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * centipawns / 400.0))
+}
+
+fn white_centipawns(sample: &Sample, calibration: &Calibration) -> f64 {
+    let score = raw_board_score(&sample.board, calibration);
+    let white_score = match sample.board.side_to_move() {
+        Color::White => score,
+        Color::Black => -score,
+    };
+    white_score as f64 / CENTIPAWN as f64
+}
+
+// Mean squared error over all samples, spread over all cores
+fn mean_error(samples: &[Sample], calibration: &Calibration, k: f64) -> f64 {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = samples.len().div_ceil(threads);
+    let total: f64 = thread::scope(|scope| {
+        let handles: Vec<_> = samples
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|sample| {
+                            let predicted = sigmoid(k, white_centipawns(sample, calibration));
+                            (sample.result - predicted).powi(2)
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Error thread panicked"))
+            .sum()
+    });
+    total / samples.len() as f64
+}
+
+// The K which best maps the untuned evaluation onto results. It stays fixed while tuning.
+fn fit_scaling_constant(samples: &[Sample], calibration: &Calibration) -> f64 {
+    let mut best = (1.0, mean_error(samples, calibration, 1.0));
+    let mut step = 0.5;
+    while step > 0.001 {
+        for k in [best.0 - step, best.0 + step] {
+            if k <= 0.0 {
+                continue;
+            }
+            let error = mean_error(samples, calibration, k);
+            if error < best.1 {
+                best = (k, error);
+            }
+        }
+        step /= 2.0;
+    }
+    best.0
+}
+
+//
+// The local search
+//
+
+fn local_search(
+    samples: &[Sample],
+    weights: EvalWeights,
+    k: f64,
+    max_passes: usize,
+    output: &str,
+) -> anyhow::Result<EvalWeights> {
+    // The weights are handled as JSON, so every integer in them is a parameter
+    let mut parameters = serde_json::to_value(&weights)?;
+    let count = integers(&mut parameters).len();
+    let mut best_error = mean_error(samples, &calibration_for(&parameters)?, k);
+    println!("{} parameters, starting error {:.6}", count, best_error);
+    for pass in 1..=max_passes {
+        let mut improved = 0;
+        for index in 0..count {
+            for step in [1, -1] {
+                nudge(&mut parameters, index, step);
+                let error = mean_error(samples, &calibration_for(&parameters)?, k);
+                if error < best_error {
+                    best_error = error;
+                    improved += 1;
+                    break;
+                }
+                nudge(&mut parameters, index, -step);
+            }
+        }
+        println!(
+            "Pass {}: error {:.6}, {} parameters improved",
+            pass, best_error, improved
+        );
+        serde_json::from_value::<EvalWeights>(parameters.clone())?.save(output)?;
+        if improved == 0 {
+            break;
+        }
+    }
+    Ok(serde_json::from_value(parameters)?)
+}
+
+fn calibration_for(parameters: &Value) -> anyhow::Result<Calibration> {
+    Ok(Calibration {
+        weights: serde_json::from_value(parameters.clone())?,
+        ..Calibration::default()
+    })
+}
+
+fn nudge(parameters: &mut Value, index: usize, step: i64) {
+    let parameter = integers(parameters).swap_remove(index);
+    let value = parameter.as_i64().expect("Weights are integers");
+    *parameter = Value::from(value + step);
+}
+
+// All tunable integers in the weights, in a fixed order
+fn integers(value: &mut Value) -> Vec<&mut Value> {
+    if value.is_i64() {
+        return vec![value];
+    }
+    match value {
+        Value::Array(values) => values.iter_mut().flat_map(integers).collect(),
+        Value::Object(map) => map
+            .iter_mut()
+            .filter(|(key, _)| !FIXED_WEIGHTS.contains(&key.as_str()))
+            .flat_map(|(_, value)| integers(value))
+            .collect(),
+        _ => vec![],
+    }
+}
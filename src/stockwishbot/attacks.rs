@@ -0,0 +1,40 @@
+// Attack bitboards for whole boards, built from the attack tables of the chess crate.
+use chess::get_bishop_moves;
+use chess::get_king_moves;
+use chess::get_knight_moves;
+use chess::get_pawn_attacks;
+use chess::get_rook_moves;
+use chess::BitBoard;
+use chess::Board;
+use chess::Color;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_PIECES;
+use chess::EMPTY;
+
+// The squares attacked by a piece on the given square, regardless of what stands on them.
+pub fn piece_attacks(piece: Piece, square: Square, color: Color, occupied: BitBoard) -> BitBoard {
+    match piece {
+        Piece::Pawn => get_pawn_attacks(square, color, !EMPTY),
+        Piece::Knight => get_knight_moves(square),
+        Piece::Bishop => get_bishop_moves(square, occupied),
+        Piece::Rook => get_rook_moves(square, occupied),
+        Piece::Queen => get_bishop_moves(square, occupied) | get_rook_moves(square, occupied),
+        Piece::King => get_king_moves(square),
+    }
+}
+
+// All squares attacked by the given kind of piece of the given player
+pub fn attacks_by_piece(board: &Board, piece: Piece, color: Color) -> BitBoard {
+    let occupied = *board.combined();
+    (board.pieces(piece) & board.color_combined(color)).fold(EMPTY, |attacks, square| {
+        attacks | piece_attacks(piece, square, color, occupied)
+    })
+}
+
+// All squares attacked by any piece of the given player
+pub fn attacks_by_color(board: &Board, color: Color) -> BitBoard {
+    ALL_PIECES.iter().fold(EMPTY, |attacks, piece| {
+        attacks | attacks_by_piece(board, *piece, color)
+    })
+}
@@ -0,0 +1,732 @@
+// Win/draw/loss bitbases for endings with few pieces, for machines without tablebase files. They are
+// generated by retrograde analysis: the checkmates, and the captures and promotions into endings that
+// are already known, are spread back through the moves leading to them. Each table covers the
+// material of one signature, such as "KRKP" with the stronger side first, and is stored on disk with
+// two bits per position. Tables are indexed as if the stronger side were white.
+use chess::get_bishop_moves;
+use chess::get_king_moves;
+use chess::get_knight_moves;
+use chess::get_rook_moves;
+use chess::BitBoard;
+use chess::Board;
+use chess::BoardBuilder;
+use chess::BoardStatus;
+use chess::CastleRights;
+use chess::Color;
+use chess::MoveGen;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::ALL_SQUARES;
+use chess::EMPTY;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use super::endgame::material_signature;
+use super::endgame::PIECE_LETTERS;
+use super::pawn_structure::relative_rank;
+use super::weights::PieceValues;
+
+// Only endings with at most this many pieces, kings included, can be generated.
+pub const MAX_BITBASE_PIECES: usize = 4;
+const PROMOTIONS: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
+// A file is named after its signature, and holds MAGIC, a little-endian u32 format version, the
+// length of the signature as a single byte, the signature itself and then the packed results.
+const MAGIC: &[u8; 4] = b"SWBB";
+const FORMAT_VERSION: u32 = 1;
+const EXTENSION: &str = "swbb";
+
+// The states of the positions while generating
+const UNKNOWN: u8 = 0;
+const WIN: u8 = 1;
+const LOSS: u8 = 2;
+const DRAW: u8 = 3;
+const ILLEGAL: u8 = 4;
+
+// The result for the player to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wdl {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Wdl {
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            1 => Wdl::Win,
+            2 => Wdl::Loss,
+            _ => Wdl::Draw,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Wdl::Draw => 0,
+            Wdl::Win => 1,
+            Wdl::Loss => 2,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Bitbases {
+    tables: HashMap<String, Bitbase>,
+}
+
+impl Bitbases {
+    // All tables found in the directory
+    pub fn load(directory: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut bitbases = Self::default();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                let table = Bitbase::from_bytes(&fs::read(&path)?)?;
+                bitbases.tables.insert(table.material.signature(), table);
+            }
+        }
+        Ok(bitbases)
+    }
+
+    pub fn save(&self, directory: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::create_dir_all(&directory)?;
+        for (signature, table) in &self.tables {
+            let path = directory
+                .as_ref()
+                .join(format!("{}.{}", signature, EXTENSION));
+            fs::write(path, table.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn signatures(&self) -> Vec<&str> {
+        let mut signatures: Vec<&str> = self.tables.keys().map(String::as_str).collect();
+        signatures.sort_unstable();
+        signatures
+    }
+
+    // Generates the table of the signature, after those of the endings it can turn into.
+    // Tables which are already there are kept.
+    pub fn generate(&mut self, signature: &str) -> anyhow::Result<()> {
+        let material = Material::parse(signature)
+            .ok_or_else(|| anyhow::anyhow!("Invalid material signature {}", signature))?
+            .canonical();
+        if material.piece_count() > MAX_BITBASE_PIECES {
+            anyhow::bail!("Bitbases have at most {} pieces", MAX_BITBASE_PIECES);
+        }
+        self.generate_material(&material);
+        Ok(())
+    }
+
+    fn generate_material(&mut self, material: &Material) {
+        if material.is_dead_draw() || self.tables.contains_key(&material.signature()) {
+            return;
+        }
+        for successor in material.successors() {
+            self.generate_material(&successor);
+        }
+        println!("Generating the {} bitbase", material.signature());
+        let table = Bitbase::generate(material, self);
+        self.tables.insert(material.signature(), table);
+    }
+
+    // The result for the player to move, or None if the board is not covered by the tables.
+    // Castling and en passant are not part of the tables.
+    pub fn probe(&self, board: &Board) -> Option<Wdl> {
+        if board.combined().popcnt() as usize > MAX_BITBASE_PIECES
+            || board.en_passant().is_some()
+            || ALL_COLORS
+                .iter()
+                .any(|&color| board.castle_rights(color) != CastleRights::NoRights)
+        {
+            return None;
+        }
+        let material = Material::parse(&material_signature(board, Color::White))?;
+        if material.is_dead_draw() {
+            return Some(Wdl::Draw);
+        }
+        let strong = if material.is_canonical() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let table = self.tables.get(&material.canonical().signature())?;
+        Some(table.get(table.index_of(board, strong)?))
+    }
+}
+
+// The pieces besides the kings, of the stronger side and then the weaker side, most valuable first
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Material {
+    sides: [Vec<Piece>; 2],
+}
+
+impl Material {
+    // The material of a signature as written by material_signature, with the first side first
+    fn parse(signature: &str) -> Option<Self> {
+        let (strong, weak) = signature.strip_prefix('K')?.split_once('K')?;
+        let side = |letters: &str| -> Option<Vec<Piece>> {
+            letters
+                .chars()
+                .map(|letter| {
+                    PIECE_LETTERS
+                        .iter()
+                        .find(|&&(_, l)| l == letter)
+                        .map(|&(piece, _)| piece)
+                })
+                .collect()
+        };
+        let mut material = Self {
+            sides: [side(strong)?, side(weak)?],
+        };
+        material.sort();
+        Some(material)
+    }
+
+    fn signature(&self) -> String {
+        let mut signature = String::new();
+        for side in &self.sides {
+            signature.push('K');
+            signature.extend(side.iter().map(|&piece| letter(piece)));
+        }
+        signature
+    }
+
+    fn sort(&mut self) {
+        for side in &mut self.sides {
+            side.sort_by_key(|&piece| letter_order(piece));
+        }
+    }
+
+    fn flipped(&self) -> Self {
+        Self {
+            sides: [self.sides[1].clone(), self.sides[0].clone()],
+        }
+    }
+
+    // Whether the first side is the stronger one. With equal material the signature decides,
+    // so that only one of the two ways round is ever used.
+    fn is_canonical(&self) -> bool {
+        let flipped = self.flipped();
+        (value(&self.sides[0]), self.signature()) >= (value(&flipped.sides[0]), flipped.signature())
+    }
+
+    fn canonical(&self) -> Self {
+        if self.is_canonical() {
+            self.clone()
+        } else {
+            self.flipped()
+        }
+    }
+
+    fn piece_count(&self) -> usize {
+        2 + self.sides[0].len() + self.sides[1].len()
+    }
+
+    // Bare kings, with at most a single minor piece, cannot mate.
+    fn is_dead_draw(&self) -> bool {
+        matches!(
+            self.sides.concat().as_slice(),
+            [] | [Piece::Bishop] | [Piece::Knight]
+        )
+    }
+
+    // The endings a single capture, promotion or capturing promotion leads to
+    fn successors(&self) -> Vec<Material> {
+        let mut successors: Vec<Material> = vec![];
+        for mover in 0..2 {
+            let other = 1 - mover;
+            let mut promotions = vec![None];
+            for (slot, &piece) in self.sides[mover].iter().enumerate() {
+                if piece == Piece::Pawn {
+                    promotions.extend(PROMOTIONS.iter().map(|&promotion| Some((slot, promotion))));
+                }
+            }
+            let captures: Vec<Option<usize>> = std::iter::once(None)
+                .chain((0..self.sides[other].len()).map(Some))
+                .collect();
+            for promotion in &promotions {
+                for capture in &captures {
+                    if promotion.is_none() && capture.is_none() {
+                        continue;
+                    }
+                    let mut next = self.clone();
+                    if let Some((slot, piece)) = *promotion {
+                        next.sides[mover][slot] = piece;
+                    }
+                    if let Some(slot) = *capture {
+                        next.sides[other].remove(slot);
+                    }
+                    next.sort();
+                    let next = next.canonical();
+                    if !successors.contains(&next) {
+                        successors.push(next);
+                    }
+                }
+            }
+        }
+        successors
+    }
+}
+
+fn letter(piece: Piece) -> char {
+    PIECE_LETTERS[letter_order(piece)].1
+}
+
+fn letter_order(piece: Piece) -> usize {
+    PIECE_LETTERS
+        .iter()
+        .position(|&(p, _)| p == piece)
+        .expect("Kings are not part of the material")
+}
+
+// With the default values, so which side of a table is the stronger one does not depend on the weights.
+fn value(pieces: &[Piece]) -> i32 {
+    let values = PieceValues::default();
+    pieces.iter().map(|&piece| values.of(piece)).sum()
+}
+
+// A position of a table is the side to move and a square per piece. The index is built from
+// whether the weaker side is to move, and then the squares: the stronger king, the weaker king,
+// and the other pieces in the order of the material. Identical pieces take their squares in
+// increasing order, so every position has a single index.
+struct Bitbase {
+    material: Material,
+    // The side (0 for the stronger, 1 for the weaker) and the piece of every square of a position
+    pieces: Vec<(usize, Piece)>,
+    // Two bits per position, four positions per byte
+    data: Vec<u8>,
+}
+
+impl Bitbase {
+    fn new(material: Material, data: Vec<u8>) -> Self {
+        let mut pieces = vec![(0, Piece::King), (1, Piece::King)];
+        for (side, side_pieces) in material.sides.iter().enumerate() {
+            pieces.extend(side_pieces.iter().map(|&piece| (side, piece)));
+        }
+        Self {
+            material,
+            pieces,
+            data,
+        }
+    }
+
+    fn size(&self) -> usize {
+        2 * 64usize.pow(self.pieces.len() as u32)
+    }
+
+    fn get(&self, index: usize) -> Wdl {
+        Wdl::from_bits((self.data[index / 4] >> (2 * (index % 4))) & 3)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let signature = self.material.signature();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(FORMAT_VERSION.to_le_bytes());
+        bytes.push(signature.len() as u8);
+        bytes.extend(signature.as_bytes());
+        bytes.extend(&self.data);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < 9 || bytes[..4] != MAGIC[..] {
+            anyhow::bail!("Not a StockWish bitbase file");
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into()?);
+        if version != FORMAT_VERSION {
+            anyhow::bail!("Unsupported bitbase format version {}", version);
+        }
+        let end = 9 + bytes[8] as usize;
+        let signature = std::str::from_utf8(
+            bytes
+                .get(9..end)
+                .ok_or_else(|| anyhow::anyhow!("The bitbase file ends too early"))?,
+        )?;
+        let material = Material::parse(signature)
+            .ok_or_else(|| anyhow::anyhow!("Invalid material signature {}", signature))?;
+        let table = Self::new(material, bytes[end..].to_vec());
+        if table.data.len() != table.size().div_ceil(4) {
+            anyhow::bail!("The {} bitbase has the wrong size", signature);
+        }
+        Ok(table)
+    }
+
+    //
+    // Indexing
+    //
+
+    fn index(&self, weak_to_move: bool, squares: &[usize]) -> usize {
+        squares
+            .iter()
+            .fold(weak_to_move as usize, |index, &square| index * 64 + square)
+    }
+
+    fn position(&self, mut index: usize) -> (bool, [usize; MAX_BITBASE_PIECES]) {
+        let mut squares = [0; MAX_BITBASE_PIECES];
+        for square in squares[..self.pieces.len()].iter_mut().rev() {
+            *square = index % 64;
+            index /= 64;
+        }
+        (index == 1, squares)
+    }
+
+    // Puts the squares of identical pieces in increasing order
+    fn canonicalize(&self, squares: &mut [usize]) {
+        let mut start = 0;
+        while start < self.pieces.len() {
+            let end = start
+                + self.pieces[start..]
+                    .iter()
+                    .take_while(|&&piece| piece == self.pieces[start])
+                    .count();
+            squares[start..end].sort_unstable();
+            start = end;
+        }
+    }
+
+    // The index of a board with the material of this table, with the stronger side of the given colour
+    fn index_of(&self, board: &Board, strong: Color) -> Option<usize> {
+        let mut squares = [0; MAX_BITBASE_PIECES];
+        for (slot, &(side, piece)) in self.pieces.iter().enumerate() {
+            let color = if side == 0 { strong } else { !strong };
+            let occurrence = self.pieces[..slot]
+                .iter()
+                .filter(|&&other| other == (side, piece))
+                .count();
+            let square = (board.pieces(piece) & board.color_combined(color)).nth(occurrence)?;
+            squares[slot] = match strong {
+                Color::White => square.to_index(),
+                Color::Black => square.to_index() ^ 56,
+            };
+        }
+        let squares = &mut squares[..self.pieces.len()];
+        self.canonicalize(squares);
+        Some(self.index(board.side_to_move() != strong, squares))
+    }
+
+    // The board of a position, with the stronger side as white, or None if it is not a legal position
+    fn board(&self, weak_to_move: bool, squares: &[usize]) -> Option<Board> {
+        let mut builder = BoardBuilder::new();
+        for (slot, (&(side, piece), &square)) in self.pieces.iter().zip(squares).enumerate() {
+            if squares[..slot].contains(&square) {
+                return None;
+            }
+            // Pawns never stand on the first or the last rank
+            if piece == Piece::Pawn && !(8..56).contains(&square) {
+                return None;
+            }
+            let color = if side == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            builder.piece(ALL_SQUARES[square], piece, color);
+        }
+        let side_to_move = if weak_to_move {
+            Color::Black
+        } else {
+            Color::White
+        };
+        builder.side_to_move(side_to_move);
+        // This also rejects the positions in which the player who just moved left their king in check
+        Board::try_from(&builder).ok()
+    }
+
+    //
+    // Generating
+    //
+
+    fn generate(material: &Material, bitbases: &Bitbases) -> Self {
+        let mut table = Self::new(material.clone(), vec![]);
+        let size = table.size();
+        let mut states = vec![UNKNOWN; size];
+        // For every position, the number of moves not yet known to lose
+        let mut remaining = vec![0u8; size];
+
+        // Every position on its own first, spread over all cores
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = size.div_ceil(threads);
+        thread::scope(|scope| {
+            let table = &table;
+            for (chunk, (states, remaining)) in states
+                .chunks_mut(chunk_size)
+                .zip(remaining.chunks_mut(chunk_size))
+                .enumerate()
+            {
+                scope.spawn(move || {
+                    for (offset, (state, count)) in
+                        states.iter_mut().zip(remaining.iter_mut()).enumerate()
+                    {
+                        (*state, *count) =
+                            table.initial_state(chunk * chunk_size + offset, bitbases);
+                    }
+                });
+            }
+        });
+
+        // Then every decided position decides the positions leading to it
+        let mut decided: Vec<usize> = (0..size)
+            .filter(|&index| states[index] == WIN || states[index] == LOSS)
+            .collect();
+        while let Some(index) = decided.pop() {
+            let won = states[index] == WIN;
+            for previous in table.predecessors(index) {
+                if states[previous] != UNKNOWN {
+                    continue;
+                }
+                if !won {
+                    states[previous] = WIN;
+                    decided.push(previous);
+                } else {
+                    remaining[previous] = remaining[previous]
+                        .checked_sub(1)
+                        .expect("More moves lose than the position has");
+                    if remaining[previous] == 0 {
+                        states[previous] = LOSS;
+                        decided.push(previous);
+                    }
+                }
+            }
+        }
+
+        // Whatever is still undecided can never be forced, so it is a draw.
+        let mut data = vec![0u8; size.div_ceil(4)];
+        for (index, &state) in states.iter().enumerate() {
+            let wdl = match state {
+                WIN => Wdl::Win,
+                LOSS => Wdl::Loss,
+                _ => Wdl::Draw,
+            };
+            data[index / 4] |= wdl.bits() << (2 * (index % 4));
+        }
+        table.data = data;
+        table
+    }
+
+    // The state of a position from its own moves, and the number of its moves not yet known to lose.
+    // Captures and promotions leave the table, so their results come from the smaller tables.
+    fn initial_state(&self, index: usize, bitbases: &Bitbases) -> (u8, u8) {
+        let (weak_to_move, squares) = self.position(index);
+        let squares = &squares[..self.pieces.len()];
+        let mut canonical = [0; MAX_BITBASE_PIECES];
+        canonical[..squares.len()].copy_from_slice(squares);
+        self.canonicalize(&mut canonical[..squares.len()]);
+        if canonical[..squares.len()] != *squares {
+            return (ILLEGAL, 0);
+        }
+        let Some(board) = self.board(weak_to_move, squares) else {
+            return (ILLEGAL, 0);
+        };
+        match board.status() {
+            BoardStatus::Checkmate => return (LOSS, 0),
+            BoardStatus::Stalemate => return (DRAW, 0),
+            BoardStatus::Ongoing => {}
+        }
+        let mut remaining = 0;
+        for chess_move in MoveGen::new_legal(&board) {
+            let leaves_table = board.piece_on(chess_move.get_dest()).is_some()
+                || chess_move.get_promotion().is_some();
+            if !leaves_table {
+                remaining += 1;
+                continue;
+            }
+            match bitbases.probe(&board.make_move_new(chess_move)) {
+                Some(Wdl::Loss) => return (WIN, 0),
+                Some(Wdl::Win) => {}
+                _ => remaining += 1,
+            }
+        }
+        if remaining == 0 {
+            (LOSS, 0)
+        } else {
+            (UNKNOWN, remaining)
+        }
+    }
+
+    // The positions in this table from which a move leads to the given position
+    fn predecessors(&self, index: usize) -> Vec<usize> {
+        let (weak_to_move, squares) = self.position(index);
+        let squares = &squares[..self.pieces.len()];
+        // The player who made the move
+        let mover_side = if weak_to_move { 0 } else { 1 };
+        let mover = if mover_side == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let occupied = squares.iter().fold(EMPTY, |occupied, &square| {
+            occupied | BitBoard::from_square(ALL_SQUARES[square])
+        });
+        let mut predecessors = vec![];
+        for (slot, &(side, piece)) in self.pieces.iter().enumerate() {
+            if side != mover_side {
+                continue;
+            }
+            let to = ALL_SQUARES[squares[slot]];
+            let origins = match piece {
+                Piece::King => get_king_moves(to),
+                Piece::Knight => get_knight_moves(to),
+                Piece::Bishop => get_bishop_moves(to, occupied),
+                Piece::Rook => get_rook_moves(to, occupied),
+                Piece::Queen => get_bishop_moves(to, occupied) | get_rook_moves(to, occupied),
+                Piece::Pawn => pawn_origins(to, mover, occupied),
+            } & !occupied;
+            for from in origins {
+                let mut previous = [0; MAX_BITBASE_PIECES];
+                previous[..squares.len()].copy_from_slice(squares);
+                previous[slot] = from.to_index();
+                let previous = &mut previous[..squares.len()];
+                self.canonicalize(previous);
+                predecessors.push(self.index(!weak_to_move, previous));
+            }
+        }
+        predecessors
+    }
+}
+
+// The squares from which a pawn of the given colour can have been pushed to the square
+fn pawn_origins(to: Square, color: Color, occupied: BitBoard) -> BitBoard {
+    let back = |square: Square| match color {
+        Color::White => square.down(),
+        Color::Black => square.up(),
+    };
+    let mut origins = EMPTY;
+    if relative_rank(to, color) < 2 {
+        return origins;
+    }
+    if let Some(single) = back(to).filter(|&from| occupied & BitBoard::from_square(from) == EMPTY) {
+        origins |= BitBoard::from_square(single);
+        // A pawn on its starting rank can move two squares at once
+        if relative_rank(to, color) == 3 {
+            if let Some(double) = back(single) {
+                origins |= BitBoard::from_square(double);
+            }
+        }
+    }
+    origins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::OnceLock;
+
+    // KPK, along with KQK and KRK which its promotions turn into
+    fn king_and_pawn_bitbases() -> &'static Bitbases {
+        static BITBASES: OnceLock<Bitbases> = OnceLock::new();
+        BITBASES.get_or_init(|| {
+            let mut bitbases = Bitbases::default();
+            bitbases.generate("KPK").unwrap();
+            bitbases
+        })
+    }
+
+    fn probe(bitbases: &Bitbases, fen: &str) -> Option<Wdl> {
+        bitbases.probe(&Board::from_str(fen).unwrap())
+    }
+
+    // Every position is won for the side with the queen or rook, unless the lone king is stalemated
+    // or can take the piece right away.
+    fn check_won_for_strong_side(signature: &str) {
+        let bitbases = king_and_pawn_bitbases();
+        let table = &bitbases.tables[signature];
+        let mut positions = 0;
+        for index in 0..table.size() {
+            let (weak_to_move, squares) = table.position(index);
+            let Some(board) = table.board(weak_to_move, &squares[..table.pieces.len()]) else {
+                continue;
+            };
+            let expected = if !weak_to_move {
+                Wdl::Win
+            } else if board.status() == BoardStatus::Checkmate {
+                Wdl::Loss
+            } else if board.status() == BoardStatus::Stalemate
+                || MoveGen::new_legal(&board)
+                    .any(|chess_move| board.piece_on(chess_move.get_dest()).is_some())
+            {
+                Wdl::Draw
+            } else {
+                Wdl::Loss
+            };
+            assert_eq!(bitbases.probe(&board), Some(expected), "{}", board);
+            positions += 1;
+        }
+        assert!(positions > 0);
+    }
+
+    #[test]
+    fn king_and_queen_against_king_is_won() {
+        check_won_for_strong_side("KQK");
+    }
+
+    #[test]
+    fn king_and_rook_against_king_is_won() {
+        check_won_for_strong_side("KRK");
+    }
+
+    #[test]
+    fn king_and_pawn_against_king() {
+        let bitbases = king_and_pawn_bitbases();
+        for (fen, expected) in [
+            // The king in front of its pawn, which only wins with the opposition
+            ("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1", Wdl::Draw),
+            ("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1", Wdl::Loss),
+            ("8/8/8/4k3/4P3/4K3/8/8 w - - 0 1", Wdl::Draw),
+            // The king on a key square in front of its pawn
+            ("4k3/8/3K4/8/4P3/8/8/8 w - - 0 1", Wdl::Win),
+            // The pawn runs away from the king
+            ("7k/8/8/P7/8/8/8/7K w - - 0 1", Wdl::Win),
+            ("8/1K6/8/4k3/P7/8/8/8 b - - 0 1", Wdl::Loss),
+            // The king reaches the corner in front of the rook pawn
+            ("k7/8/1K6/P7/8/8/8/8 b - - 0 1", Wdl::Draw),
+            ("8/8/5k2/8/8/P7/8/7K w - - 0 1", Wdl::Draw),
+            // The same positions with the colours reversed
+            ("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1", Wdl::Draw),
+            ("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1", Wdl::Loss),
+        ] {
+            assert_eq!(probe(bitbases, fen), Some(expected), "{}", fen);
+        }
+    }
+
+    #[test]
+    #[ignore = "generates several four-piece tables, run with --release"]
+    fn pawn_endings_can_be_won_by_either_side() {
+        let mut bitbases = Bitbases::default();
+        bitbases.generate("KPKP").unwrap();
+        for (fen, expected) in [
+            // White promotes with check long before the black pawn gets anywhere
+            ("7k/3P4/8/p7/8/2K5/8/8 w - - 0 1", Wdl::Win),
+            ("7k/3P4/8/p7/8/2K5/8/8 b - - 0 1", Wdl::Loss),
+            // The same for black
+            ("8/8/2k5/8/P7/8/3p4/7K b - - 0 1", Wdl::Win),
+            ("8/8/2k5/8/P7/8/3p4/7K w - - 0 1", Wdl::Loss),
+        ] {
+            assert_eq!(probe(&bitbases, fen), Some(expected), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn saved_tables_load_back() {
+        let bitbases = king_and_pawn_bitbases();
+        let directory =
+            std::env::temp_dir().join(format!("stockwish-bitbases-{}", std::process::id()));
+        bitbases.save(&directory).unwrap();
+        let loaded = Bitbases::load(&directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(loaded.signatures(), bitbases.signatures());
+        for signature in bitbases.signatures() {
+            assert_eq!(
+                loaded.tables[signature].data,
+                bitbases.tables[signature].data
+            );
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// Specialised evaluation of endgames the general evaluation handles badly. Each evaluator is registered
+// under the material signature of the stronger side, e.g. "KBNK", and scores from that side's point-of-view.
+use chess::get_rank;
+use chess::BitBoard;
+use chess::Board;
+use chess::Color;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::EMPTY;
+
+use super::evaluation::CENTIPAWN;
+use super::evaluation::DARK_SQUARES;
+use super::pawn_structure::distance;
+use super::pawn_structure::relative_rank;
+use super::weights::PieceValues;
+
+// Well below the mate scores, but well above anything the general evaluation returns, in centipawns.
+// The pushes reward driving the losing king towards the edge, the corner and the winning king.
+const KNOWN_WIN: i32 = 10_000;
+const PUSH_TO_EDGE: i32 = 20;
+const PUSH_TO_CORNER: i32 = 30;
+const PUSH_CLOSE: i32 = 10;
+// Only boards with at most this many pieces, kings included, have a registered evaluator.
+const MAX_ENDGAME_PIECES: u32 = 4;
+
+struct Endgame {
+    signature: &'static str,
+    evaluate: fn(&Board, Color, &PieceValues) -> i32,
+}
+
+const ENDGAMES: [Endgame; 7] = [
+    Endgame {
+        signature: "KQK",
+        evaluate: mop_up,
+    },
+    Endgame {
+        signature: "KRK",
+        evaluate: mop_up,
+    },
+    Endgame {
+        signature: "KBNK",
+        evaluate: bishop_and_knight,
+    },
+    Endgame {
+        signature: "KPK",
+        evaluate: king_and_pawn,
+    },
+    Endgame {
+        signature: "KNNK",
+        evaluate: draw,
+    },
+    Endgame {
+        signature: "KBK",
+        evaluate: draw,
+    },
+    Endgame {
+        signature: "KNK",
+        evaluate: draw,
+    },
+];
+
+// The score of a specialised evaluator, from the point-of-view of the player who's turn it is,
+// or None if there is no evaluator for the material on the board.
+pub fn endgame_score(board: &Board, piece_values: &PieceValues) -> Option<i32> {
+    if board.combined().popcnt() > MAX_ENDGAME_PIECES {
+        return None;
+    }
+    for strong in ALL_COLORS {
+        let signature = material_signature(board, strong);
+        if let Some(endgame) = ENDGAMES.iter().find(|e| e.signature == signature) {
+            let score = (endgame.evaluate)(board, strong, piece_values) * CENTIPAWN;
+            return Some(if strong == board.side_to_move() {
+                score
+            } else {
+                -score
+            });
+        }
+    }
+    None
+}
+
+// The score of a board the bitbases know to be won, from the point-of-view of the player to move.
+// A specialised evaluator knows best how to make progress. Otherwise the lone king is driven to the
+// edge and the pawns are pushed to promotion.
+pub fn won_endgame_score(board: &Board, winner: Color, piece_values: &PieceValues) -> i32 {
+    let signature = material_signature(board, winner);
+    let specialised = ENDGAMES
+        .iter()
+        .find(|e| e.signature == signature)
+        .map(|endgame| (endgame.evaluate)(board, winner, piece_values))
+        .filter(|&score| score >= KNOWN_WIN);
+    let score = specialised.unwrap_or_else(|| {
+        let pawns = board.pieces(Piece::Pawn) & board.color_combined(winner);
+        let promotion = pawns
+            .map(|pawn| 10 * relative_rank(pawn, winner) as i32)
+            .sum::<i32>();
+        mop_up(board, winner, piece_values) - material(board, !winner, piece_values) + promotion
+    }) * CENTIPAWN;
+    if winner == board.side_to_move() {
+        score
+    } else {
+        -score
+    }
+}
+
+// The letters of the pieces besides the king in a signature, from most to least valuable
+pub(super) const PIECE_LETTERS: [(Piece, char); 5] = [
+    (Piece::Queen, 'Q'),
+    (Piece::Rook, 'R'),
+    (Piece::Bishop, 'B'),
+    (Piece::Knight, 'N'),
+    (Piece::Pawn, 'P'),
+];
+
+// The pieces of the given side followed by those of the other side, from most to least valuable.
+pub(super) fn material_signature(board: &Board, strong: Color) -> String {
+    let mut signature = String::new();
+    for color in [strong, !strong] {
+        signature.push('K');
+        for (piece, letter) in PIECE_LETTERS {
+            let count = (board.pieces(piece) & board.color_combined(color)).popcnt();
+            signature.extend(std::iter::repeat_n(letter, count as usize));
+        }
+    }
+    signature
+}
+
+//
+// The evaluators
+//
+
+fn draw(_board: &Board, _strong: Color, _piece_values: &PieceValues) -> i32 {
+    0
+}
+
+// Drive the lone king to the edge of the board, and bring our own king closer to help mate it.
+fn mop_up(board: &Board, strong: Color, piece_values: &PieceValues) -> i32 {
+    let strong_king = board.king_square(strong);
+    let weak_king = board.king_square(!strong);
+    KNOWN_WIN + material(board, strong, piece_values) + PUSH_TO_EDGE * centre_distance(weak_king)
+        - PUSH_CLOSE * distance(strong_king, weak_king)
+}
+
+// Mate is only possible in the corners of the same colour as the bishop.
+fn bishop_and_knight(board: &Board, strong: Color, piece_values: &PieceValues) -> i32 {
+    let bishops = board.pieces(Piece::Bishop) & board.color_combined(strong);
+    let corners = if bishops & DARK_SQUARES != EMPTY {
+        [Square::A1, Square::H8]
+    } else {
+        [Square::H1, Square::A8]
+    };
+    let weak_king = board.king_square(!strong);
+    let corner_distance = corners
+        .iter()
+        .map(|&corner| distance(weak_king, corner))
+        .min()
+        .unwrap_or(0);
+    mop_up(board, strong, piece_values) - PUSH_TO_CORNER * corner_distance
+}
+
+// The textbook rules: the pawn wins if the defending king cannot catch it, or if our king
+// controls a key square in front of it. Rook pawns are drawn if the defending king gets next to the
+// corner before our king shuts it out.
+fn king_and_pawn(board: &Board, strong: Color, piece_values: &PieceValues) -> i32 {
+    let pawn = match (board.pieces(Piece::Pawn) & board.color_combined(strong)).next() {
+        Some(pawn) => pawn,
+        None => return 0,
+    };
+    let strong_king = board.king_square(strong);
+    let weak_king = board.king_square(!strong);
+    let weak_to_move = board.side_to_move() != strong;
+    let rank = relative_rank(pawn, strong);
+    let promotion = Square::make_square(
+        match strong {
+            Color::White => chess::Rank::Eighth,
+            Color::Black => chess::Rank::First,
+        },
+        pawn.get_file(),
+    );
+    let won = KNOWN_WIN + piece_values.pawn + 10 * rank as i32;
+
+    // The defending king takes the undefended pawn
+    if weak_to_move && distance(weak_king, pawn) == 1 && distance(strong_king, pawn) > 1 {
+        return 0;
+    }
+    // The rule of the square. A pawn on its starting rank can move two squares at once.
+    let pawn_moves = 7 - std::cmp::max(rank, 2) as i32;
+    let king_moves = distance(weak_king, promotion) - weak_to_move as i32;
+    if king_moves > pawn_moves {
+        return won;
+    }
+    let file = pawn.get_file().to_index();
+    if file == 0 || file == 7 {
+        return if defends_corner(board, strong, promotion) {
+            0
+        } else {
+            won
+        };
+    }
+    if key_squares(pawn, strong) & BitBoard::from_square(strong_king) != EMPTY {
+        return won;
+    }
+    0
+}
+
+// Whether the defending king holds the promotion corner of a rook pawn. Our king shuts it out from
+// the squares next to the corner on the neighbouring file. The defending king holds the corner once
+// it is next to the corner, or next to one of those squares, before our king gets there.
+fn defends_corner(board: &Board, strong: Color, promotion: Square) -> bool {
+    let strong_king = board.king_square(strong);
+    let weak_king = board.king_square(!strong);
+    let towards_centre = match promotion.get_file().to_index() {
+        0 => chess::File::B,
+        _ => chess::File::G,
+    };
+    let behind = match strong {
+        Color::White => chess::Rank::Seventh,
+        Color::Black => chess::Rank::Second,
+    };
+    let beside = Square::make_square(promotion.get_rank(), towards_centre);
+    let diagonal = Square::make_square(behind, towards_centre);
+    // Moves until our king is on one of those squares, and until the defending king is next to the
+    // corner or next to one of those squares.
+    let strong_moves = std::cmp::min(
+        distance(strong_king, beside),
+        distance(strong_king, diagonal),
+    );
+    let weak_moves = [promotion, beside, diagonal]
+        .iter()
+        .map(|&square| distance(weak_king, square) - 1)
+        .min()
+        .unwrap_or(0)
+        .max(0);
+    // Whoever is to move gets there first when the distances are equal.
+    if board.side_to_move() == strong {
+        weak_moves < strong_moves
+    } else {
+        weak_moves <= strong_moves
+    }
+}
+
+// The squares from which our king escorts the pawn to promotion, whatever the defending king does.
+fn key_squares(pawn: Square, strong: Color) -> BitBoard {
+    let rank = relative_rank(pawn, strong);
+    let ahead = if rank >= 4 { 1..=2 } else { 2..=2 };
+    let mut squares = EMPTY;
+    for steps in ahead {
+        let target_rank = rank + steps;
+        if target_rank > 7 {
+            continue;
+        }
+        let absolute_rank = match strong {
+            Color::White => target_rank,
+            Color::Black => 7 - target_rank,
+        };
+        let files = chess::get_file(pawn.get_file()) | chess::get_adjacent_files(pawn.get_file());
+        squares |= files & get_rank(chess::Rank::from_index(absolute_rank));
+    }
+    squares
+}
+
+//
+// Helpers
+//
+
+fn material(board: &Board, color: Color, piece_values: &PieceValues) -> i32 {
+    (*board.color_combined(color))
+        .filter_map(|square| board.piece_on(square))
+        .map(|piece| piece_values.of(piece))
+        .sum()
+}
+
+// 0 for the four centre squares, up to 6 in the corners
+fn centre_distance(square: Square) -> i32 {
+    let file = square.get_file().to_index() as i32;
+    let rank = square.get_rank().to_index() as i32;
+    ((2 * file - 7).abs() + (2 * rank - 7).abs() - 2) / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn white_pawn_score(fen: &str) -> i32 {
+        let board = Board::from_str(fen).expect("Invalid FEN");
+        king_and_pawn(&board, Color::White, &PieceValues::default())
+    }
+
+    #[test]
+    fn king_and_pawn_wins() {
+        for fen in [
+            // Our king controls a key square
+            "4k3/8/4K3/8/4P3/8/8/8 w - - 0 1",
+            // The defending king is outside the square of the pawn
+            "7k/8/8/P7/8/8/8/7K b - - 0 1",
+            // Our king shuts the defending king out of the corner
+            "8/1K6/8/4k3/P7/8/8/8 b - - 0 1",
+            "8/5k2/2K5/8/P7/8/8/8 w - - 0 1",
+        ] {
+            assert!(white_pawn_score(fen) >= KNOWN_WIN, "{} is won", fen);
+        }
+    }
+
+    #[test]
+    fn king_and_pawn_draws() {
+        for fen in [
+            // The defending king has the opposition in front of the pawn
+            "8/8/8/4k3/4P3/4K3/8/8 w - - 0 1",
+            // The defending king takes the pawn
+            "8/8/8/8/3Pk3/8/8/7K b - - 0 1",
+            // The defending king is in the corner of the rook pawn
+            "k7/8/1K6/P7/8/8/8/8 b - - 0 1",
+            // The defending king reaches the corner first
+            "8/8/5k2/8/8/P7/8/7K w - - 0 1",
+        ] {
+            assert_eq!(white_pawn_score(fen), 0, "{} is drawn", fen);
+        }
+    }
+}
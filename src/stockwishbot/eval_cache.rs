@@ -0,0 +1,82 @@
+// A lossy hash table of static evaluations, so positions seen again (in a later iteration of the
+// iterative deepening, or by transposition) are not evaluated from scratch. Each slot keeps only the
+// most recent position which hashed to it.
+use chess::Board;
+use chess::Color;
+
+const EVAL_CACHE_SIZE: usize = 1 << 18;
+
+// Draw scores depend on the side to move at the root, so the same board gets a different key for
+// each root side.
+const BLACK_ROOT_KEY: u64 = 0x9e37_79b9_7f4a_7c15;
+
+#[derive(Clone)]
+pub struct EvalCache {
+    entries: Vec<Option<(u64, i32)>>,
+    probes: u64,
+    hits: u64,
+}
+
+impl Default for EvalCache {
+    fn default() -> Self {
+        Self {
+            entries: vec![None; EVAL_CACHE_SIZE],
+            probes: 0,
+            hits: 0,
+        }
+    }
+}
+
+impl EvalCache {
+    // The cached score of the board, or the score computed by evaluate, which is then cached.
+    pub fn probe(
+        &mut self,
+        board: &Board,
+        root_side: Option<Color>,
+        evaluate: impl FnOnce() -> i32,
+    ) -> i32 {
+        let key = match root_side {
+            Some(Color::Black) => board.get_hash() ^ BLACK_ROOT_KEY,
+            _ => board.get_hash(),
+        };
+        self.probes += 1;
+        let slot = &mut self.entries[(key as usize) % EVAL_CACHE_SIZE];
+        match *slot {
+            Some((cached_key, score)) if cached_key == key => {
+                self.hits += 1;
+                score
+            }
+            _ => {
+                let score = evaluate();
+                *slot = Some((key, score));
+                score
+            }
+        }
+    }
+
+    // Forget all scores, for when the evaluation itself changes.
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    pub fn reset_statistics(&mut self) {
+        self.probes = 0;
+        self.hits = 0;
+    }
+
+    pub fn probes(&self) -> u64 {
+        self.probes
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    // The fraction of probes answered from the cache since the statistics were last reset
+    pub fn hit_rate(&self) -> f32 {
+        match self.probes {
+            0 => 0.0,
+            probes => self.hits as f32 / probes as f32,
+        }
+    }
+}
@@ -1,46 +1,92 @@
 // Evaluation of a board state. Usually used for leaf nodes in the game tree. Positive values are good for white,
 // negative values are good for black.
+use chess::get_adjacent_files;
+use chess::get_bishop_moves;
+use chess::get_file;
+use chess::get_knight_moves;
+use chess::get_pawn_attacks;
+use chess::get_rank;
+use chess::get_rook_moves;
 use chess::BitBoard;
 use chess::Board;
 use chess::BoardStatus;
-use chess::ALL_SQUARES;
+use chess::Color;
+use chess::Piece;
+use chess::Rank;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::EMPTY;
+use serde::Deserialize;
+use serde::Serialize;
+use std::ops::Add;
+use std::ops::AddAssign;
+use std::ops::Mul;
+use std::ops::Sub;
 
-use super::cache::insert_in_cache_if_better;
-use super::cache::SWCache;
+use super::bitbase::Wdl;
 use super::cache::Score;
-use super::cache::TopTargets;
+use super::endgame::endgame_score;
+use super::endgame::won_endgame_score;
+use super::eval_cache::EvalCache;
+use super::evaluator::Evaluator;
+use super::incremental::IncrementalEval;
+use super::king_safety::king_safety;
+use super::material::endgame_scale;
+use super::material::material_imbalance;
+use super::material::SCALE_NORMAL;
 use super::move_ordering::moves_toward_quiescence;
+use super::pawn_structure::forward_ranks;
+use super::pawn_structure::pawn_attacks;
+use super::pawn_structure::pawn_structure;
+use super::pawn_structure::relative_rank;
+use super::stockwish::DrawKind;
+use super::stockwish::EvaluationMode;
+use super::threats::threats;
+use super::trace::EvalTrace;
+use super::weights::PieceValues;
 use super::Calibration;
 
-const PIECE_VALUE_SCALE: i32 = 12;
-const POSITIONAL_SCALE: i32 = 1;
-const QUEEN_VALUE: i32 = 900;
-const ROOK_VALUE: i32 = 500;
-const BISHOP_VALUE: i32 = 330;
-const KNIGHT_VALUE: i32 = 320;
-const PAWN_VALUE: i32 = 100;
+// One hundredth of a pawn, in the units returned by the evaluation.
+pub const CENTIPAWN: i32 = 12;
+pub const DARK_SQUARES: BitBoard = BitBoard(0xAA55_AA55_AA55_AA55);
+
+// Everything the quiescence search needs, apart from the position, the window and the incremental state.
+pub struct QuiescenceContext<'a> {
+    pub eval_cache: &'a mut EvalCache,
+    pub evaluator: &'a dyn Evaluator,
+    pub calibration: &'a Calibration,
+    // The side to move at the root of the search, from whose point-of-view draws are scored
+    pub root_side: Color,
+}
 
 pub fn quiescent_board_score(
     board: &Board,
-    cache: &mut SWCache,
+    ctx: &mut QuiescenceContext,
     alpha: i32,
     beta: i32,
-    calibration: Calibration,
-) -> i32 {
+    state: &IncrementalEval,
+) -> Score {
     // Evaluate a board. We only actually evaluate quiescent board states, so we run through
-    // a new game tree, with no max depth, only considering captures.
+    // a new game tree, with no max depth, only considering captures. The caller caches the score,
+    // once any evaluation noise has been added.
     // TODO: Currently using alpha-beta pruning, but I hear delta-pruning is good at this?
-    let score = quiescent_alpha_beta(board, alpha, beta, calibration);
-    // TODO: We could potentially find some good targets, but it would only involve captures,
-    // so probably not so useful for general tree search.
-    insert_in_cache_if_better(board, 0, &score, TopTargets::new(0), cache);
-    score.into()
+    quiescent_alpha_beta(board, ctx, alpha, beta, state)
 }
 
 // NOTE: Currently not using a cache. I think this is best, but tests should be done.
-fn quiescent_alpha_beta(board: &Board, _alpha: i32, beta: i32, calibration: Calibration) -> Score {
-    // Check if current raw_board_score is enough to cause a beta-cutoff
-    let eval = raw_board_score(board, calibration);
+fn quiescent_alpha_beta(
+    board: &Board,
+    ctx: &mut QuiescenceContext,
+    _alpha: i32,
+    beta: i32,
+    state: &IncrementalEval,
+) -> Score {
+    // Check if the current evaluation is enough to cause a beta-cutoff
+    let root_side = Some(ctx.root_side);
+    let (evaluator, calibration) = (ctx.evaluator, ctx.calibration);
+    let eval = ctx.eval_cache.probe(board, root_side, || {
+        evaluator.evaluate_incremental(board, calibration, root_side, state)
+    });
     if beta <= eval {
         return Score::LowerBound(eval);
     }
@@ -49,8 +95,14 @@ fn quiescent_alpha_beta(board: &Board, _alpha: i32, beta: i32, calibration: Cali
     for capture in moves_toward_quiescence(board) {
         // TODO: If current eval + captured piece (+ some margin) is above alpha, quiesce further down.
         // Otherwise set best_value = max(best_value, that-thing-above^^)
-        let child_score =
-            -quiescent_alpha_beta(&board.make_move_new(capture), -beta, -alpha, calibration);
+        let child_state = state.make_move(board, capture, calibration);
+        let child_score = -quiescent_alpha_beta(
+            &board.make_move_new(capture),
+            ctx,
+            -beta,
+            -alpha,
+            &child_state,
+        );
         let child_score_numeric = i32::from(child_score);
         if beta <= child_score_numeric {
             return Score::LowerBound(child_score_numeric);
@@ -61,343 +113,471 @@ fn quiescent_alpha_beta(board: &Board, _alpha: i32, beta: i32, calibration: Cali
     Score::Exact(alpha)
 }
 
-pub fn raw_board_score(board: &Board, calibration: Calibration) -> i32 {
-    // This function must return scores from the point-of-view of the player who's turn it is.
+// The position at the end of the principal variation of the quiescence search, where no capture
+// is worth making any more. Its static evaluation is the quiescent score of the original position.
+pub fn quiet_position(board: &Board, calibration: &Calibration) -> Board {
+    quiet_alpha_beta(board, i32::MIN + 1, i32::MAX, calibration).1
+}
+
+fn quiet_alpha_beta(
+    board: &Board,
+    alpha: i32,
+    beta: i32,
+    calibration: &Calibration,
+) -> (i32, Board) {
+    let eval = raw_board_score(board, calibration);
+    if beta <= eval {
+        return (eval, *board);
+    }
+    let mut alpha = std::cmp::max(alpha, eval);
+    let mut best = (eval, *board);
+    for capture in moves_toward_quiescence(board) {
+        let (child_score, leaf) =
+            quiet_alpha_beta(&board.make_move_new(capture), -beta, -alpha, calibration);
+        if -child_score > alpha {
+            alpha = -child_score;
+            best = (alpha, leaf);
+            if beta <= alpha {
+                break;
+            }
+        }
+    }
+    best
+}
+
+pub fn raw_board_score(board: &Board, calibration: &Calibration) -> i32 {
+    evaluate_with_trace(board, calibration).score
+}
+
+// The same, with the material and piece-square tables kept up to date by the search, and draws
+// scored for the side to move at the root of the search.
+pub fn incremental_board_score(
+    board: &Board,
+    calibration: &Calibration,
+    root_side: Option<Color>,
+    state: &IncrementalEval,
+) -> i32 {
+    trace_with_state(board, calibration, root_side, state).score
+}
+
+// The evaluation of a board, together with all the terms that went into it.
+pub fn evaluate_with_trace(board: &Board, calibration: &Calibration) -> EvalTrace {
+    trace_with_state(
+        board,
+        calibration,
+        None,
+        &IncrementalEval::new(board, calibration),
+    )
+}
+
+fn trace_with_state(
+    board: &Board,
+    calibration: &Calibration,
+    root_side: Option<Color>,
+    state: &IncrementalEval,
+) -> EvalTrace {
+    // The score must be from the point-of-view of the player who's turn it is.
+    let shortcut = rule_shortcut(board, calibration, root_side)
+        .or_else(|| bitbase_shortcut(board, calibration, root_side))
+        // Some endgames are known better than the general evaluation knows them
+        .or_else(|| {
+            endgame_score(board, &calibration.weights.piece_values)
+                .map(|score| ("specialised endgame", score))
+        })
+        .or_else(|| match (&calibration.evaluation, &state.accumulator) {
+            // The network replaces all the classical terms
+            (EvaluationMode::Network(network), Some(accumulator)) => Some((
+                "network",
+                network.evaluate(accumulator, board.side_to_move()),
+            )),
+            _ => None,
+        });
+    let mut trace = EvalTrace::default();
+    match shortcut {
+        Some((reason, score)) => {
+            trace.shortcut = Some(reason);
+            trace.score = score;
+        }
+        None => trace.score = ongoing_raw_board_score(board, calibration, state, &mut trace),
+    }
+    trace
+}
+
+// The score of a position which the rules decide: checkmate, stalemate or insufficient material.
+// Draws are scored for the given root side, as by Calibration::draw_score.
+pub fn rule_score(
+    board: &Board,
+    calibration: &Calibration,
+    root_side: Option<Color>,
+) -> Option<i32> {
+    rule_shortcut(board, calibration, root_side).map(|(_, score)| score)
+}
+
+fn rule_shortcut(
+    board: &Board,
+    calibration: &Calibration,
+    root_side: Option<Color>,
+) -> Option<(&'static str, i32)> {
     match board.status() {
         // If it is currently a checkmate, it is a very bad thing for the current player
-        BoardStatus::Checkmate => i32::MIN + 1,
-        // A stalemate is evenly meh.
-        BoardStatus::Stalemate => 0,
-        _ => ongoing_raw_board_score(board, calibration),
+        BoardStatus::Checkmate => Some(("checkmate", i32::MIN + 1)),
+        // A stalemate is evenly meh, unless we have contempt for the opponent.
+        BoardStatus::Stalemate => Some((
+            "stalemate",
+            calibration.draw_score(DrawKind::Stalemate, board.side_to_move(), root_side),
+        )),
+        _ if insufficient_material(board) => Some((
+            "insufficient material",
+            calibration.draw_score(
+                DrawKind::InsufficientMaterial,
+                board.side_to_move(),
+                root_side,
+            ),
+        )),
+        _ => None,
+    }
+}
+
+// Endings the bitbases know to be drawn, or won for one side
+fn bitbase_shortcut(
+    board: &Board,
+    calibration: &Calibration,
+    root_side: Option<Color>,
+) -> Option<(&'static str, i32)> {
+    let side_to_move = board.side_to_move();
+    let piece_values = &calibration.weights.piece_values;
+    let score = match calibration.bitbases.as_ref()?.probe(board)? {
+        Wdl::Draw => calibration.draw_score(DrawKind::Tablebase, side_to_move, root_side),
+        Wdl::Win => won_endgame_score(board, side_to_move, piece_values),
+        Wdl::Loss => won_endgame_score(board, !side_to_move, piece_values),
+    };
+    Some(("bitbase", score))
+}
+
+pub fn insufficient_material(board: &Board) -> bool {
+    // Neither side can mate with only kings and a single minor piece, or with bishops all on one square colour.
+    let heavy_pieces_and_pawns =
+        board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    if heavy_pieces_and_pawns != EMPTY {
+        return false;
+    }
+    let knights = *board.pieces(Piece::Knight);
+    let bishops = *board.pieces(Piece::Bishop);
+    if (knights | bishops).popcnt() <= 1 {
+        return true;
     }
+    knights == EMPTY && (bishops & DARK_SQUARES == EMPTY || bishops & !DARK_SQUARES == EMPTY)
 }
 
-fn ongoing_raw_board_score(board: &Board, calibration: Calibration) -> i32 {
+fn ongoing_raw_board_score(
+    board: &Board,
+    calibration: &Calibration,
+    state: &IncrementalEval,
+    trace: &mut EvalTrace,
+) -> i32 {
     // This function must return scores from the point-of-view of the player who's turn it is.
-    let material = sum_piece_square_tables(board);
-    // let mobility = mobility_score(board);
+    let weights = &calibration.weights;
+    let positional =
+        |scores: [TaperedScore; 2]| scores.map(|score| score * weights.positional_scale);
+    trace.phase = state.phase(&weights.piece_values);
+    trace.material = state.material;
+    trace.piece_square = state.piece_square;
+    trace.imbalance = material_imbalance(board, &weights.imbalance)
+        .map(|score| score * weights.piece_value_scale);
+    trace.pawn_structure = positional(pawn_structure(board, &weights.pawns));
+    trace.mobility = positional(mobility(board, &weights.mobility));
+    trace.king_safety = positional(king_safety(board, &weights.king_safety));
+    trace.pieces = positional(piece_terms(board, &weights.pieces));
+    trace.threats = positional(threats(board, &weights.threats));
+    let score = trace.total();
+    // Drawish endgames keep only part of the endgame score
+    trace.endgame_scale = endgame_scale(
+        board,
+        &weights.scaling,
+        &weights.piece_values,
+        score.endgame,
+    );
+    let score = TaperedScore::new(
+        score.midgame,
+        score.endgame * trace.endgame_scale / SCALE_NORMAL,
+    );
     let turn = match board.side_to_move() {
         chess::Color::White => 1,
         chess::Color::Black => -1,
     };
-    turn * material
+    turn * score.taper(trace.phase)
 }
 
-// TODO: Wait until we only search quiescent positions (no checks)
-// fn mobility_score(board: &Board) -> i32 {
-//     let current_player_mobility = MoveGen::new_legal(board).len();
-//     let opposing_player_mobility = if let Some(reversed_board) = board.null_move() {
-//         MoveGen::new_legal(&reversed_board).len()
-//     } else {
-//         // Current player is in check.
-//         let all_checkers = |b: &Board| b.checkers()
-//         let board_without_checkers = board
-//     }
-
-//     current_player_mobility as i32 - opposing_player_mobility as i32
-// }
-
-enum GamePhase {
-    Opening,
-    MiddleGame,
-    Endgame,
+// Bonus for the number of squares each piece can move to, indexed by that number.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MobilityWeights {
+    pub knight: [TaperedScore; 9],
+    pub bishop: [TaperedScore; 14],
+    pub rook: [TaperedScore; 15],
+    pub queen: [TaperedScore; 28],
 }
 
-fn game_phase(board: &Board) -> GamePhase {
-    let total_material: i32 = ALL_SQUARES
-        .map(|s| board.piece_on(s))
-        .map(piece_value)
-        .into_iter()
-        .sum();
-    if total_material > 6800 {
-        GamePhase::Opening
-    } else if total_material > 3000 {
-        GamePhase::MiddleGame
-    } else {
-        GamePhase::Endgame
+impl Default for MobilityWeights {
+    fn default() -> Self {
+        Self {
+            knight: tapered_table(
+                [-20, -12, -4, 0, 4, 8, 12, 15, 18],
+                [-25, -15, -6, 0, 5, 10, 14, 17, 20],
+            ),
+            bishop: tapered_table(
+                [-20, -12, -4, 0, 4, 8, 12, 15, 18, 20, 22, 24, 26, 28],
+                [-25, -15, -6, 0, 5, 10, 15, 20, 24, 27, 30, 32, 34, 36],
+            ),
+            rook: tapered_table(
+                [-15, -10, -5, -2, 0, 2, 4, 6, 8, 10, 12, 13, 14, 15, 16],
+                [-30, -20, -10, -5, 0, 5, 10, 15, 20, 24, 28, 31, 34, 36, 38],
+            ),
+            queen: tapered_table(
+                [
+                    -10, -8, -6, -4, -2, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 10, 11, 11, 12, 12, 13,
+                    13, 14, 14, 15, 15, 15,
+                ],
+                [
+                    -20, -15, -10, -6, -3, 0, 3, 6, 9, 12, 14, 16, 18, 20, 22, 24, 25, 26, 27, 28,
+                    29, 30, 31, 32, 33, 34, 35, 35,
+                ],
+            ),
+        }
     }
 }
 
-#[inline(always)]
-pub fn piece_value(p: Option<chess::Piece>) -> i32 {
-    match p {
-        Some(chess::Piece::Queen) => 900,
-        Some(chess::Piece::Rook) => 500,
-        Some(chess::Piece::Bishop) => 330,
-        Some(chess::Piece::Knight) => 320,
-        Some(chess::Piece::Pawn) => 100,
-        _ => 0, // The king is covered by the checkmate rules.
+pub const fn tapered_table<const N: usize>(
+    midgame: [i32; N],
+    endgame: [i32; N],
+) -> [TaperedScore; N] {
+    let mut table = [TaperedScore::new(0, 0); N];
+    let mut i = 0;
+    while i < N {
+        table[i] = TaperedScore::new(midgame[i], endgame[i]);
+        i += 1;
     }
+    table
 }
 
-struct PieceSquareTable {
-    cells: [i32; 64], // Row-major: a1, b1, c1, ...
+// Mobility, indexed by Color::to_index(). We count the squares attacked by each piece, regardless of
+// pins and checks, except squares occupied by our own pieces or attacked by enemy pawns.
+fn mobility(board: &Board, weights: &MobilityWeights) -> [TaperedScore; 2] {
+    ALL_COLORS.map(|color| mobility_for_color(board, weights, color))
 }
 
-impl PieceSquareTable {
-    pub const fn new(offset: i32, offset_scale: i32, scale: i32, vals: [i32; 64]) -> Self {
-        let mut cells = vals;
-        let mut i = 0;
-        while i < 64 {
-            cells[i] = offset * offset_scale + scale * cells[i];
-            i += 1;
+fn mobility_for_color(board: &Board, weights: &MobilityWeights, color: Color) -> TaperedScore {
+    let own = board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let available = !(own | pawn_attacks(enemy_pawns, !color));
+    let occupied = *board.combined();
+    let count = |attacks: BitBoard| (attacks & available).popcnt() as usize;
+    let mut score = TaperedScore::default();
+    for square in board.pieces(Piece::Knight) & own {
+        score += weights.knight[count(get_knight_moves(square))];
+    }
+    for square in board.pieces(Piece::Bishop) & own {
+        score += weights.bishop[count(get_bishop_moves(square, occupied))];
+    }
+    for square in board.pieces(Piece::Rook) & own {
+        score += weights.rook[count(get_rook_moves(square, occupied))];
+    }
+    for square in board.pieces(Piece::Queen) & own {
+        let attacks = get_bishop_moves(square, occupied) | get_rook_moves(square, occupied);
+        score += weights.queen[count(attacks)];
+    }
+    score
+}
+
+// Weights of the positional terms for specific pieces
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceWeights {
+    pub bishop_pair: TaperedScore,
+    // For every own pawn on the squares of the bishop's colour
+    pub bad_bishop: TaperedScore,
+    // A knight on the opponent's half of the board, defended by a pawn, which no enemy pawn can chase away
+    pub knight_outpost: TaperedScore,
+    pub rook_open_file: TaperedScore,
+    pub rook_semi_open_file: TaperedScore,
+    // A rook on the seventh rank, cutting off the enemy king or attacking enemy pawns
+    pub rook_on_seventh: TaperedScore,
+    pub connected_rooks: TaperedScore,
+    // A bishop on a7 or h7, locked in by a pawn on b6 or g6
+    pub trapped_bishop: TaperedScore,
+    // A rook in the corner, locked in by its own king which can no longer castle
+    pub trapped_rook: TaperedScore,
+}
+
+impl Default for PieceWeights {
+    fn default() -> Self {
+        Self {
+            bishop_pair: TaperedScore::new(30, 50),
+            bad_bishop: TaperedScore::new(-3, -5),
+            knight_outpost: TaperedScore::new(20, 10),
+            rook_open_file: TaperedScore::new(25, 10),
+            rook_semi_open_file: TaperedScore::new(12, 5),
+            rook_on_seventh: TaperedScore::new(15, 30),
+            connected_rooks: TaperedScore::new(10, 5),
+            trapped_bishop: TaperedScore::new(-100, -100),
+            trapped_rook: TaperedScore::new(-50, -25),
         }
-        Self { cells }
     }
+}
+
+// Piece-specific terms, indexed by Color::to_index()
+fn piece_terms(board: &Board, weights: &PieceWeights) -> [TaperedScore; 2] {
+    ALL_COLORS.map(|color| piece_terms_for_color(board, weights, color))
+}
+
+fn piece_terms_for_color(board: &Board, weights: &PieceWeights, color: Color) -> TaperedScore {
+    let own = *board.color_combined(color);
+    let own_pawns = board.pieces(Piece::Pawn) & own;
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let bishops = board.pieces(Piece::Bishop) & own;
+    let knights = board.pieces(Piece::Knight) & own;
+    let rooks = board.pieces(Piece::Rook) & own;
+    let mut score = TaperedScore::default();
 
-    pub const fn new_raw(vals: [i32; 64]) -> Self {
-        Self { cells: vals }
+    // Bishops
+    if bishops.popcnt() >= 2 {
+        score += weights.bishop_pair;
+    }
+    for square in bishops {
+        let same_colour_squares = if DARK_SQUARES & BitBoard::from_square(square) != EMPTY {
+            DARK_SQUARES
+        } else {
+            !DARK_SQUARES
+        };
+        score += weights.bad_bishop * (own_pawns & same_colour_squares).popcnt() as i32;
+    }
+    let (trap_squares, trapping_pawns) = match color {
+        Color::White => ([Square::A7, Square::H7], [Square::B6, Square::G6]),
+        Color::Black => ([Square::A2, Square::H2], [Square::B3, Square::G3]),
+    };
+    for (trap_square, trapping_pawn) in trap_squares.into_iter().zip(trapping_pawns) {
+        if bishops & BitBoard::from_square(trap_square) != EMPTY
+            && enemy_pawns & BitBoard::from_square(trapping_pawn) != EMPTY
+        {
+            score += weights.trapped_bishop;
+        }
     }
 
-    pub fn dot(&self, bitboard: &BitBoard) -> i32 {
-        bitboard
-            .into_iter()
-            .map(|sq| self.cells[63 - sq.to_index()])
-            .sum()
+    // Knights
+    for square in knights {
+        let rank = relative_rank(square, color);
+        let supported = get_pawn_attacks(square, !color, own_pawns) != EMPTY;
+        let chasers = forward_ranks(square, color) & get_adjacent_files(square.get_file());
+        if (3..=5).contains(&rank) && supported && enemy_pawns & chasers == EMPTY {
+            score += weights.knight_outpost;
+        }
     }
 
-    pub const fn change_color(&self) -> Self {
-        // The mirror is chunk-reversed, to account for the king and queen that change position.
-        Self::new_raw([
-            self.cells[56],
-            self.cells[57],
-            self.cells[58],
-            self.cells[59],
-            self.cells[60],
-            self.cells[61],
-            self.cells[62],
-            self.cells[63],
-            self.cells[48],
-            self.cells[49],
-            self.cells[50],
-            self.cells[51],
-            self.cells[52],
-            self.cells[53],
-            self.cells[54],
-            self.cells[55],
-            self.cells[40],
-            self.cells[41],
-            self.cells[42],
-            self.cells[43],
-            self.cells[44],
-            self.cells[45],
-            self.cells[46],
-            self.cells[47],
-            self.cells[32],
-            self.cells[33],
-            self.cells[34],
-            self.cells[35],
-            self.cells[36],
-            self.cells[37],
-            self.cells[38],
-            self.cells[39],
-            self.cells[24],
-            self.cells[25],
-            self.cells[26],
-            self.cells[27],
-            self.cells[28],
-            self.cells[29],
-            self.cells[30],
-            self.cells[31],
-            self.cells[16],
-            self.cells[17],
-            self.cells[18],
-            self.cells[19],
-            self.cells[20],
-            self.cells[21],
-            self.cells[22],
-            self.cells[23],
-            self.cells[8],
-            self.cells[9],
-            self.cells[10],
-            self.cells[11],
-            self.cells[12],
-            self.cells[13],
-            self.cells[14],
-            self.cells[15],
-            self.cells[0],
-            self.cells[57],
-            self.cells[58],
-            self.cells[59],
-            self.cells[60],
-            self.cells[61],
-            self.cells[62],
-            self.cells[57],
-        ])
+    // Rooks
+    let occupied = *board.combined();
+    let enemy_king = board.king_square(!color);
+    for square in rooks {
+        let file = get_file(square.get_file());
+        if own_pawns & file == EMPTY {
+            if enemy_pawns & file == EMPTY {
+                score += weights.rook_open_file;
+            } else {
+                score += weights.rook_semi_open_file;
+            }
+        }
+        if relative_rank(square, color) == 6
+            && (relative_rank(enemy_king, color) == 7
+                || enemy_pawns & get_rank(square.get_rank()) != EMPTY)
+        {
+            score += weights.rook_on_seventh;
+        }
+    }
+    if rooks.popcnt() == 2 {
+        let first = rooks.to_square();
+        if get_rook_moves(first, occupied) & rooks != EMPTY {
+            score += weights.connected_rooks;
+        }
     }
+    if trapped_rook(board, color, rooks, own_pawns) {
+        score += weights.trapped_rook;
+    }
+    score
 }
 
-fn sum_piece_square_tables(board: &Board) -> i32 {
-    let (white_pawns, white_knights, white_bishops, white_rooks, white_queens, white_king) =
-        piece_square_tables_for_color(board, chess::Color::White);
-    let (black_pawns, black_knights, black_bishops, black_rooks, black_queens, black_king) =
-        piece_square_tables_for_color(board, chess::Color::Black);
-    WHITE_PAWN.dot(&white_pawns) - BLACK_PAWN.dot(&black_pawns) + WHITE_KNIGHT.dot(&white_knights)
-        - BLACK_KNIGHT.dot(&black_knights)
-        + WHITE_BISHOP.dot(&white_bishops)
-        - BLACK_BISHOP.dot(&black_bishops)
-        + WHITE_ROOK.dot(&white_rooks)
-        - BLACK_ROOK.dot(&black_rooks)
-        + WHITE_QUEEN.dot(&white_queens)
-        - BLACK_QUEEN.dot(&black_queens)
-        + WHITE_KING_OPENING.dot(&white_king)
-        - BLACK_KING_OPENING.dot(&black_king)
+fn trapped_rook(board: &Board, color: Color, rooks: BitBoard, own_pawns: BitBoard) -> bool {
+    let king = board.king_square(color);
+    let back_rank = match color {
+        Color::White => Rank::First,
+        Color::Black => Rank::Eighth,
+    };
+    if king.get_rank() != back_rank {
+        return false;
+    }
+    let castle_rights = board.castle_rights(color);
+    let king_file = king.get_file().to_index();
+    (rooks & get_rank(back_rank)).any(|rook| {
+        let rook_file = rook.get_file().to_index();
+        // The rook is stuck behind its own pawn, and the king blocks its way along the back rank
+        let stuck = own_pawns & get_file(rook.get_file()) != EMPTY;
+        let kingside =
+            (5..=6).contains(&king_file) && rook_file > king_file && !castle_rights.has_kingside();
+        let queenside =
+            (1..=3).contains(&king_file) && rook_file < king_file && !castle_rights.has_queenside();
+        stuck && (kingside || queenside)
+    })
 }
 
-fn piece_square_tables_for_color(
-    board: &Board,
-    color: chess::Color,
-) -> (
-    chess::BitBoard,
-    chess::BitBoard,
-    chess::BitBoard,
-    chess::BitBoard,
-    chess::BitBoard,
-    chess::BitBoard,
-) {
-    let pawns = board.color_combined(color) & board.pieces(chess::Piece::Pawn);
-    let knights = board.color_combined(color) & board.pieces(chess::Piece::Knight);
-    let bishops = board.color_combined(color) & board.pieces(chess::Piece::Bishop);
-    let rooks = board.color_combined(color) & board.pieces(chess::Piece::Rook);
-    let queens = board.color_combined(color) & board.pieces(chess::Piece::Queen);
-    let king = board.color_combined(color) & board.pieces(chess::Piece::King);
-    (pawns, knights, bishops, rooks, queens, king)
+// A score which depends on the phase of the game. The midgame part counts fully when all pieces are
+// on the board, and the endgame part counts fully once only kings and pawns are left.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaperedScore {
+    pub midgame: i32,
+    pub endgame: i32,
+}
+
+impl TaperedScore {
+    pub const fn new(midgame: i32, endgame: i32) -> Self {
+        Self { midgame, endgame }
+    }
+
+    pub fn taper(&self, phase: i32) -> i32 {
+        (self.midgame * phase + self.endgame * (PHASE_MIDGAME - phase)) / PHASE_MIDGAME
+    }
 }
 
-// Pawns
-const WHITE_PAWN: PieceSquareTable = PieceSquareTable::new(
-    PAWN_VALUE,
-    PIECE_VALUE_SCALE,
-    POSITIONAL_SCALE,
-    [
-        0, 0, 0, 0, 0, 0, 0, 0, //
-        50, 50, 50, 50, 50, 50, 50, 50, //
-        10, 10, 20, 30, 30, 20, 10, 10, //
-        5, 5, 10, 25, 25, 10, 5, 5, //
-        0, 0, 0, 20, 20, 0, 0, 0, //
-        5, -5, -10, 0, 0, -10, -5, 5, //
-        5, 10, 10, -20, -20, 10, 10, 5, //
-        0, 0, 0, 0, 0, 0, 0, 0, //
-    ],
-);
-const WHITE_KNIGHT: PieceSquareTable = PieceSquareTable::new(
-    KNIGHT_VALUE,
-    PIECE_VALUE_SCALE,
-    POSITIONAL_SCALE,
-    [
-        -50, -40, -30, -30, -30, -30, -40, -50, //
-        -40, -20, 0, 0, 0, 0, -20, -40, //
-        -30, 0, 10, 15, 15, 10, 0, -30, //
-        -30, 5, 15, 20, 20, 15, 5, -30, //
-        -30, 0, 15, 20, 20, 15, 0, -30, //
-        -30, 5, 10, 15, 15, 10, 5, -30, //
-        -40, -20, 0, 5, 5, 0, -20, -40, //
-        -50, -40, -30, -30, -30, -30, -40, -50, //
-    ],
-);
-const WHITE_BISHOP: PieceSquareTable = PieceSquareTable::new(
-    BISHOP_VALUE,
-    PIECE_VALUE_SCALE,
-    POSITIONAL_SCALE,
-    [
-        -20, -10, -10, -10, -10, -10, -10, -20, //
-        -10, 0, 0, 0, 0, 0, 0, -10, //
-        -10, 0, 5, 10, 10, 5, 0, -10, //
-        -10, 5, 5, 10, 10, 5, 5, -10, //
-        -10, 0, 10, 10, 10, 10, 0, -10, //
-        -10, 10, 10, 10, 10, 10, 10, -10, //
-        -10, 5, 0, 0, 0, 0, 5, -10, //
-        -20, -10, -10, -10, -10, -10, -10, -20, //
-    ],
-);
-const WHITE_ROOK: PieceSquareTable = PieceSquareTable::new(
-    ROOK_VALUE,
-    PIECE_VALUE_SCALE,
-    POSITIONAL_SCALE,
-    [
-        0, 0, 0, 0, 0, 0, 0, 0, //
-        5, 10, 10, 10, 10, 10, 10, 5, //
-        -5, 0, 0, 0, 0, 0, 0, -5, //
-        -5, 0, 0, 0, 0, 0, 0, -5, //
-        -5, 0, 0, 0, 0, 0, 0, -5, //
-        -5, 0, 0, 0, 0, 0, 0, -5, //
-        -5, 0, 0, 0, 0, 0, 0, -5, //
-        0, 0, 0, 5, 5, 0, 0, 0, //
-    ],
-);
-const WHITE_QUEEN: PieceSquareTable = PieceSquareTable::new(
-    QUEEN_VALUE,
-    PIECE_VALUE_SCALE,
-    POSITIONAL_SCALE,
-    [
-        -20, -10, -10, -5, -5, -10, -10, -20, //
-        -10, 0, 0, 0, 0, 0, 0, -10, //
-        -10, 0, 5, 5, 5, 5, 0, -10, //
-        -5, 0, 5, 5, 5, 5, 0, -5, //
-        0, 0, 5, 5, 5, 5, 0, -5, //
-        -10, 5, 5, 5, 5, 5, 0, -10, //
-        -10, 0, 5, 0, 0, 0, 0, -10, //
-        -20, -10, -10, -5, -5, -10, -10, -20, //
-    ],
-);
-const WHITE_KING_OPENING: PieceSquareTable = PieceSquareTable::new(
-    0,
-    PIECE_VALUE_SCALE,
-    POSITIONAL_SCALE,
-    [
-        -30, -40, -40, -50, -50, -40, -40, -30, //
-        -30, -40, -40, -50, -50, -40, -40, -30, //
-        -30, -40, -40, -50, -50, -40, -40, -30, //
-        -30, -40, -40, -50, -50, -40, -40, -30, //
-        -20, -30, -30, -40, -40, -30, -30, -20, //
-        -10, -20, -20, -20, -20, -20, -20, -10, //
-        20, 20, 0, 0, 0, 0, 20, 20, //
-        20, 30, 10, 0, 0, 10, 30, 20, //
-    ],
-);
-const WHITE_KING_ENDGAME: PieceSquareTable = PieceSquareTable::new(
-    0,
-    PIECE_VALUE_SCALE,
-    POSITIONAL_SCALE,
-    [
-        -50, -40, -30, -20, -20, -30, -40, -50, //
-        -30, -20, -10, 0, 0, -10, -20, -30, //
-        -30, -10, 20, 30, 30, 20, -10, -30, //
-        -30, -10, 30, 40, 40, 30, -10, -30, //
-        -30, -10, 30, 40, 40, 30, -10, -30, //
-        -30, -10, 20, 30, 30, 20, -10, -30, //
-        -30, -30, 0, 0, 0, 0, -30, -30, //
-        -50, -30, -30, -30, -30, -30, -30, -50, //
-    ],
-);
-
-const BLACK_PAWN: PieceSquareTable = WHITE_PAWN.change_color();
-const BLACK_KNIGHT: PieceSquareTable = WHITE_KNIGHT.change_color();
-const BLACK_BISHOP: PieceSquareTable = WHITE_BISHOP.change_color();
-const BLACK_ROOK: PieceSquareTable = WHITE_ROOK.change_color();
-const BLACK_QUEEN: PieceSquareTable = WHITE_QUEEN.change_color();
-const BLACK_KING_OPENING: PieceSquareTable = WHITE_KING_OPENING.change_color();
-const BLACK_KING_ENDGAME: PieceSquareTable = WHITE_KING_ENDGAME.change_color();
-
-// fn pawn_singlet_position(board: &Board, phase: GamePhase) -> f32 {
-//     // TODO: Make below const
-//     let BLACK_PAWN: PieceSquareTable = WHITE_PAWN.mirror_copy();
-//     let BLACK_KNIGHT: PieceSquareTable = WHITE_KNIGHT.mirror_copy();
-//     let BLACK_BISHOP: PieceSquareTable = WHITE_BISHOP.mirror_copy();
-//     let BLACK_ROOK: PieceSquareTable = WHITE_ROOK.mirror_copy();
-//     let BLACK_QUEEN: PieceSquareTable = WHITE_QUEEN.mirror_copy();
-//     let BLACK_KING_OPENING: PieceSquareTable = WHITE_KING_OPENING.mirror_copy();
-//     let BLACK_KING_ENDGAME: PieceSquareTable = WHITE_KING_ENDGAME.mirror_copy();
-//     let white_pawns = board.color_combined(chess::Color::White) & board.pieces(chess::Piece::Pawn);
-//     let black_pawns = board.color_combined(chess::Color::Black) & board.pieces(chess::Piece::Pawn);
-//     // match phase {
-//     //     GamePhase::Opening => {
-//     //         OPENING_WHITE_PAWN_HM.dot(&white_pawns) - OPENING_BLACK_PAWN_HM.dot(&black_pawns)
-//     //     }
-//     //     GamePhase::MiddleGame => 0.0,
-//     //     GamePhase::Endgame => {
-//     //         LATE_WHITE_PAWN_HM.dot(&white_pawns) - LATE_BLACK_PAWN_HM.dot(&black_pawns)
-//     //     }
-//     // }
-//     0.0
-// }
+impl Add for TaperedScore {
+    type Output = TaperedScore;
+
+    fn add(self, other: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.midgame + other.midgame, self.endgame + other.endgame)
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = TaperedScore;
+
+    fn sub(self, other: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.midgame - other.midgame, self.endgame - other.endgame)
+    }
+}
+
+impl AddAssign for TaperedScore {
+    fn add_assign(&mut self, other: TaperedScore) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<i32> for TaperedScore {
+    type Output = TaperedScore;
+
+    fn mul(self, factor: i32) -> TaperedScore {
+        TaperedScore::new(self.midgame * factor, self.endgame * factor)
+    }
+}
+
+// The phase runs continuously from 0 (bare endgame) to PHASE_MIDGAME (all pieces on the board),
+// and is computed from the non-pawn material of both players, valued by the weights.
+pub const PHASE_MIDGAME: i32 = 256;
+
+pub fn phase_from_material(non_pawn_material: i32, piece_values: &PieceValues) -> i32 {
+    let full = piece_values.starting_non_pawn_material().max(1);
+    std::cmp::min(non_pawn_material, full) * PHASE_MIDGAME / full
+}
@@ -0,0 +1,77 @@
+// The evaluation used by the search, as a trait, so experimental evaluations can be plugged into
+// StockWish without touching the search itself.
+use chess::Board;
+use chess::Color;
+use chess::ALL_PIECES;
+
+use super::evaluation::incremental_board_score;
+use super::evaluation::rule_score;
+use super::incremental::IncrementalEval;
+use super::stockwish::Calibration;
+
+pub trait Evaluator: Send + Sync {
+    // The score of a board in evaluation units, from the point-of-view of the player to move.
+    // Draws are scored for the side to move at the root of the search, if there is one.
+    fn evaluate(&self, board: &Board, calibration: &Calibration, root_side: Option<Color>) -> i32;
+
+    // The same, given the incremental state the search keeps up to date for this board. Evaluators
+    // which have no use for it can rely on this default.
+    fn evaluate_incremental(
+        &self,
+        board: &Board,
+        calibration: &Calibration,
+        root_side: Option<Color>,
+        _state: &IncrementalEval,
+    ) -> i32 {
+        self.evaluate(board, calibration, root_side)
+    }
+}
+
+// The rules, the known endgames, and then the classical terms or the network of the calibration.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultEvaluator;
+
+impl Evaluator for DefaultEvaluator {
+    fn evaluate(&self, board: &Board, calibration: &Calibration, root_side: Option<Color>) -> i32 {
+        incremental_board_score(
+            board,
+            calibration,
+            root_side,
+            &IncrementalEval::new(board, calibration),
+        )
+    }
+
+    fn evaluate_incremental(
+        &self,
+        board: &Board,
+        calibration: &Calibration,
+        root_side: Option<Color>,
+        state: &IncrementalEval,
+    ) -> i32 {
+        incremental_board_score(board, calibration, root_side, state)
+    }
+}
+
+// Only the rules and the piece values of the calibration's weights. Mostly useful as a baseline.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&self, board: &Board, calibration: &Calibration, root_side: Option<Color>) -> i32 {
+        if let Some(score) = rule_score(board, calibration, root_side) {
+            return score;
+        }
+        let weights = &calibration.weights;
+        let side = board.color_combined(board.side_to_move());
+        let material: i32 = ALL_PIECES
+            .iter()
+            .map(|&piece| {
+                let pieces = board.pieces(piece);
+                let own = (pieces & side).popcnt() as i32;
+                let other = pieces.popcnt() as i32 - own;
+                (own - other) * weights.piece_values.of(piece)
+            })
+            .sum();
+        material * weights.piece_value_scale
+    }
+}
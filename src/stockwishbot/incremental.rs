@@ -0,0 +1,142 @@
+// The parts of the evaluation which only depend on where each piece stands: material, piece-square
+// tables, the game phase and the hidden layer of the network. The search keeps them up to date move
+// by move, instead of summing over all pieces again at every leaf.
+use chess::Board;
+use chess::ChessMove;
+use chess::Color;
+use chess::File;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::ALL_PIECES;
+
+use super::evaluation::phase_from_material;
+use super::evaluation::TaperedScore;
+use super::nnue::Accumulator;
+use super::nnue::Network;
+use super::stockwish::EvaluationMode;
+use super::weights::EvalWeights;
+use super::weights::PieceValues;
+use super::Calibration;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IncrementalEval {
+    // Indexed by Color::to_index(), in evaluation units with the scales of the weights applied
+    pub material: [TaperedScore; 2],
+    // Further indexed by Piece::to_index()
+    pub piece_square: [[TaperedScore; 6]; 2],
+    // Non-pawn material of both players, as used for the game phase
+    phase_material: i32,
+    // The hidden layer of the network, when the network evaluation is used
+    pub accumulator: Option<Accumulator>,
+}
+
+impl IncrementalEval {
+    pub fn new(board: &Board, calibration: &Calibration) -> Self {
+        let mut eval = Self::default();
+        for color in ALL_COLORS {
+            for piece in ALL_PIECES {
+                for square in board.pieces(piece) & board.color_combined(color) {
+                    eval.update(&calibration.weights, None, piece, color, square, 1);
+                }
+            }
+        }
+        if let EvaluationMode::Network(network) = &calibration.evaluation {
+            eval.accumulator = Some(network.accumulator(board));
+        }
+        eval
+    }
+
+    // The state after the move is made on the board. The board is the position before the move.
+    pub fn make_move(
+        &self,
+        board: &Board,
+        chess_move: ChessMove,
+        calibration: &Calibration,
+    ) -> Self {
+        let mut eval = self.clone();
+        let weights = &calibration.weights;
+        let network = match &calibration.evaluation {
+            EvaluationMode::Network(network) => Some(network.as_ref()),
+            EvaluationMode::Classical => None,
+        };
+        let color = board.side_to_move();
+        let source = chess_move.get_source();
+        let dest = chess_move.get_dest();
+        let piece = board.piece_on(source).expect("A move starts on a piece");
+        let mut update = |piece, color, square, sign| {
+            eval.update(weights, network, piece, color, square, sign);
+        };
+        update(piece, color, source, -1);
+        if let Some(captured) = board.piece_on(dest) {
+            update(captured, !color, dest, -1);
+        } else if piece == Piece::Pawn && source.get_file() != dest.get_file() {
+            // En passant takes the pawn next to us
+            let captured = Square::make_square(source.get_rank(), dest.get_file());
+            update(Piece::Pawn, !color, captured, -1);
+        }
+        update(chess_move.get_promotion().unwrap_or(piece), color, dest, 1);
+        // Castling is a king move of two files, which takes the rook along
+        let files_moved = source
+            .get_file()
+            .to_index()
+            .abs_diff(dest.get_file().to_index());
+        if piece == Piece::King && files_moved == 2 {
+            let (rook_from, rook_to) = match dest.get_file() {
+                File::G => (File::H, File::F),
+                _ => (File::A, File::D),
+            };
+            let rank = source.get_rank();
+            update(Piece::Rook, color, Square::make_square(rank, rook_from), -1);
+            update(Piece::Rook, color, Square::make_square(rank, rook_to), 1);
+        }
+        eval
+    }
+
+    pub fn phase(&self, piece_values: &PieceValues) -> i32 {
+        phase_from_material(self.phase_material, piece_values)
+    }
+
+    // Adds (sign 1) or removes (sign -1) a piece
+    fn update(
+        &mut self,
+        weights: &EvalWeights,
+        network: Option<&Network>,
+        piece: Piece,
+        color: Color,
+        square: Square,
+        sign: i32,
+    ) {
+        let piece_value = weights.piece_values.of(piece);
+        let value = piece_value * weights.piece_value_scale;
+        self.material[color.to_index()] += TaperedScore::new(value, value) * sign;
+        self.piece_square[color.to_index()][piece.to_index()] +=
+            piece_square_value(weights, piece, color, square) * sign;
+        if piece != Piece::Pawn {
+            self.phase_material += piece_value * sign;
+        }
+        if let (Some(network), Some(accumulator)) = (network, &mut self.accumulator) {
+            network.update(accumulator, piece, color, square, sign as i16);
+        }
+    }
+}
+
+// The tables are laid out as seen from white's side of the board, with the eighth rank first.
+// Black uses the same tables, mirrored between the first and the eighth rank.
+fn piece_square_value(
+    weights: &EvalWeights,
+    piece: Piece,
+    color: Color,
+    square: Square,
+) -> TaperedScore {
+    let rank = square.get_rank().to_index();
+    let row = match color {
+        Color::White => 7 - rank,
+        Color::Black => rank,
+    };
+    let column = square.get_file().to_index();
+    TaperedScore::new(
+        weights.midgame_tables.of(piece)[row][column],
+        weights.endgame_tables.of(piece)[row][column],
+    ) * weights.positional_scale
+}
@@ -0,0 +1,161 @@
+// King safety of both players. This mostly matters in the midgame, while the opponent
+// still has enough pieces to mount an attack, so most terms have no endgame part.
+use chess::get_bishop_moves;
+use chess::get_file;
+use chess::get_king_moves;
+use chess::get_knight_moves;
+use chess::get_rook_moves;
+use chess::BitBoard;
+use chess::Board;
+use chess::Color;
+use chess::File;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::EMPTY;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::attacks::attacks_by_color;
+use super::attacks::piece_attacks;
+use super::evaluation::TaperedScore;
+use super::pawn_structure::forward_ranks;
+
+// The danger table covers this many attack units. Any more count as the last entry.
+const DANGER_UNITS: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KingSafetyWeights {
+    // Indexed by the number of ranks between the king and the closest pawn in front of it, on the king's
+    // file and the two files next to it. Index 0 means there is no such pawn.
+    pub pawn_shield: [i32; 8],
+    pub pawn_storm: [i32; 8],
+    // Files near the king without our own pawns, with or without enemy pawns
+    pub semi_open_file: i32,
+    pub open_file: i32,
+    // Indexed by Piece::to_index(). How dangerous an attacker of the king zone is,
+    // and how dangerous it is if it can give a safe check.
+    pub attack_weight: [i32; 6],
+    pub safe_check: [i32; 6],
+    // The midgame penalty, indexed by the sum of attack weights. It grows faster than the sum: a few
+    // attackers are a nuisance, but many attackers together can mate.
+    pub danger: [i32; DANGER_UNITS],
+}
+
+impl Default for KingSafetyWeights {
+    fn default() -> Self {
+        Self {
+            pawn_shield: [-20, 20, 10, 3, 0, 0, 0, 0],
+            pawn_storm: [0, -5, -25, -15, -8, -3, 0, 0],
+            semi_open_file: -12,
+            open_file: -25,
+            attack_weight: [0, 2, 2, 3, 5, 0],
+            safe_check: [0, 3, 2, 4, 6, 0],
+            danger: [
+                0, 3, 12, 27, 48, 75, 108, 147, 192, 243, 300, 363, 432, 507, 588, 675, 768, 867,
+                972, 1000, 1000, 1000, 1000, 1000, 1000, 1000, 1000, 1000, 1000, 1000, 1000, 1000,
+            ],
+        }
+    }
+}
+
+// Indexed by Color::to_index(), each from that player's point-of-view.
+pub fn king_safety(board: &Board, weights: &KingSafetyWeights) -> [TaperedScore; 2] {
+    ALL_COLORS.map(|color| king_safety_for_color(board, weights, color))
+}
+
+fn king_safety_for_color(board: &Board, weights: &KingSafetyWeights, color: Color) -> TaperedScore {
+    let king = board.king_square(color);
+    let pawn_structure = pawn_shelter(board, weights, king, color);
+    let danger = king_danger(board, weights, king, color);
+    TaperedScore::new(pawn_structure - danger, -danger / 4)
+}
+
+fn pawn_shelter(board: &Board, weights: &KingSafetyWeights, king: Square, color: Color) -> i32 {
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & board.color_combined(!color);
+    let in_front = forward_ranks(king, color);
+    let king_file = king.get_file().to_index();
+    let mut score = 0;
+    for file_index in king_file.saturating_sub(1)..=std::cmp::min(king_file + 1, 7) {
+        let file = get_file(File::from_index(file_index));
+        score += weights.pawn_shield[closest_rank_distance(own_pawns & file & in_front, king)];
+        score += weights.pawn_storm[closest_rank_distance(enemy_pawns & file & in_front, king)];
+        if own_pawns & file == EMPTY {
+            score += if enemy_pawns & file == EMPTY {
+                weights.open_file
+            } else {
+                weights.semi_open_file
+            };
+        }
+    }
+    score
+}
+
+fn closest_rank_distance(pawns: BitBoard, king: Square) -> usize {
+    let king_rank = king.get_rank().to_index() as i32;
+    pawns
+        .map(|square| (square.get_rank().to_index() as i32 - king_rank).unsigned_abs() as usize)
+        .min()
+        .unwrap_or(0)
+}
+
+// The enemy pieces attacking the squares around the king, and the safe checks they can give.
+fn king_danger(board: &Board, weights: &KingSafetyWeights, king: Square, color: Color) -> i32 {
+    let occupied = *board.combined();
+    let enemy = *board.color_combined(!color);
+    let zone = get_king_moves(king) | BitBoard::from_square(king);
+    // Checks are safe if the checking square is not defended, and not blocked by the attacker's own pieces
+    let safe = !(attacks_by_color(board, color) | enemy);
+    let bishop_checks = get_bishop_moves(king, occupied);
+    let rook_checks = get_rook_moves(king, occupied);
+    let mut attackers = 0;
+    let mut units = 0;
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let mut all_attacks = EMPTY;
+        for square in board.pieces(piece) & enemy {
+            let attacks = piece_attacks(piece, square, !color, occupied);
+            if attacks & zone != EMPTY {
+                attackers += 1;
+                units += weights.attack_weight[piece.to_index()];
+            }
+            all_attacks |= attacks;
+        }
+        let checks = match piece {
+            Piece::Knight => get_knight_moves(king),
+            Piece::Bishop => bishop_checks,
+            Piece::Rook => rook_checks,
+            _ => bishop_checks | rook_checks,
+        };
+        if all_attacks & checks & safe != EMPTY {
+            units += weights.safe_check[piece.to_index()];
+        }
+    }
+    // A lone attacker is not enough to get at the king
+    if attackers < 2 {
+        return 0;
+    }
+    weights.danger[(units.max(0) as usize).min(DANGER_UNITS - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn white_king_safety(fen: &str) -> TaperedScore {
+        let board = Board::from_str(fen).expect("Invalid FEN");
+        king_safety(&board, &KingSafetyWeights::default())[Color::White.to_index()]
+    }
+
+    #[test]
+    fn more_attackers_are_more_dangerous() {
+        // The queen alone, then with the knight, then with the rook as well
+        let queen = white_king_safety("6k1/8/8/8/7q/8/5PPP/6K1 w - - 0 1");
+        let knight = white_king_safety("6k1/8/8/8/6nq/8/5PPP/6K1 w - - 0 1");
+        let rook = white_king_safety("5rk1/8/8/8/6nq/8/5PPP/6K1 w - - 0 1");
+        assert!(knight.midgame < queen.midgame);
+        assert!(rook.midgame < knight.midgame);
+        assert!(rook.endgame < knight.endgame);
+    }
+}
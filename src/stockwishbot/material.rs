@@ -0,0 +1,176 @@
+// Material imbalance and endgame scaling. The fixed piece values of the piece-square tables do not
+// account for the rest of the material on the board, and some endgames are drawish no matter how
+// much material one player is ahead.
+use chess::get_file;
+use chess::BitBoard;
+use chess::Board;
+use chess::Color;
+use chess::File;
+use chess::Piece;
+use chess::ALL_COLORS;
+use chess::EMPTY;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::evaluation::TaperedScore;
+use super::evaluation::DARK_SQUARES;
+use super::weights::PieceValues;
+
+// Imbalance terms, in centipawns.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImbalanceWeights {
+    // Knights get better with more pawns on the board, and rooks worse, relative to five pawns.
+    pub knight_per_pawn: i32,
+    pub rook_per_pawn: i32,
+    pub rook_pair: i32,
+    // Minor pieces with pawns work well together against a rook
+    pub exchange_compensation: i32,
+}
+
+impl Default for ImbalanceWeights {
+    fn default() -> Self {
+        Self {
+            knight_per_pawn: 6,
+            rook_per_pawn: -12,
+            rook_pair: -16,
+            exchange_compensation: 25,
+        }
+    }
+}
+
+// Endgame scale factors. SCALE_NORMAL leaves the endgame score alone, 0 makes it a draw.
+pub const SCALE_NORMAL: i32 = 64;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScaleWeights {
+    pub opposite_bishops: i32,
+    pub opposite_bishops_with_pieces: i32,
+    pub pawnless_small_advantage: i32,
+    pub rook_ending_one_flank: i32,
+}
+
+impl Default for ScaleWeights {
+    fn default() -> Self {
+        Self {
+            opposite_bishops: 24,
+            opposite_bishops_with_pieces: 48,
+            pawnless_small_advantage: 4,
+            rook_ending_one_flank: 40,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct MaterialCount {
+    pawns: i32,
+    knights: i32,
+    bishops: i32,
+    rooks: i32,
+    queens: i32,
+}
+
+impl MaterialCount {
+    fn new(board: &Board, color: Color) -> Self {
+        let count = |piece| (board.pieces(piece) & board.color_combined(color)).popcnt() as i32;
+        Self {
+            pawns: count(Piece::Pawn),
+            knights: count(Piece::Knight),
+            bishops: count(Piece::Bishop),
+            rooks: count(Piece::Rook),
+            queens: count(Piece::Queen),
+        }
+    }
+
+    fn minors(&self) -> i32 {
+        self.knights + self.bishops
+    }
+
+    // Material of everything but pawns, in centipawns
+    fn non_pawn_material(&self, values: &PieceValues) -> i32 {
+        self.knights * values.knight
+            + self.bishops * values.bishop
+            + self.rooks * values.rook
+            + self.queens * values.queen
+    }
+}
+
+// The imbalance in centipawns, indexed by Color::to_index(). This is the same in all phases of the game.
+pub fn material_imbalance(board: &Board, weights: &ImbalanceWeights) -> [TaperedScore; 2] {
+    ALL_COLORS.map(|color| {
+        let own = MaterialCount::new(board, color);
+        let other = MaterialCount::new(board, !color);
+        let imbalance = imbalance_for(weights, &own, &other);
+        TaperedScore::new(imbalance, imbalance)
+    })
+}
+
+fn imbalance_for(weights: &ImbalanceWeights, own: &MaterialCount, other: &MaterialCount) -> i32 {
+    let mut score = own.knights * weights.knight_per_pawn * (own.pawns - 5);
+    score += own.rooks * weights.rook_per_pawn * (own.pawns - 5);
+    if own.rooks >= 2 {
+        score += weights.rook_pair;
+    }
+    if own.rooks < other.rooks && own.minors() > other.minors() {
+        score += weights.exchange_compensation;
+    }
+    score
+}
+
+// How much of the endgame score the stronger side gets to keep, out of SCALE_NORMAL.
+pub fn endgame_scale(
+    board: &Board,
+    weights: &ScaleWeights,
+    piece_values: &PieceValues,
+    endgame_score: i32,
+) -> i32 {
+    let strong_color = if endgame_score >= 0 {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let strong = MaterialCount::new(board, strong_color);
+    let weak = MaterialCount::new(board, !strong_color);
+
+    // Without pawns, a small material advantage is usually not enough to win
+    if strong.pawns == 0
+        && strong.non_pawn_material(piece_values) - weak.non_pawn_material(piece_values)
+            <= piece_values.bishop
+    {
+        return weights.pawnless_small_advantage;
+    }
+
+    // Opposite-coloured bishops
+    if strong.bishops == 1 && weak.bishops == 1 && opposite_coloured_bishops(board) {
+        let only_bishops = strong.knights + strong.rooks + strong.queens == 0
+            && weak.knights + weak.rooks + weak.queens == 0;
+        return if only_bishops {
+            weights.opposite_bishops
+        } else {
+            weights.opposite_bishops_with_pieces
+        };
+    }
+
+    // Rook endings with at most a pawn more, and all pawns on one side of the board
+    let rooks_only = |m: &MaterialCount| m.rooks == 1 && m.minors() + m.queens == 0;
+    if rooks_only(&strong) && rooks_only(&weak) && strong.pawns - weak.pawns <= 1 {
+        let pawns = *board.pieces(Piece::Pawn);
+        if pawns & queenside() == EMPTY || pawns & !queenside() == EMPTY {
+            return weights.rook_ending_one_flank;
+        }
+    }
+    SCALE_NORMAL
+}
+
+fn opposite_coloured_bishops(board: &Board) -> bool {
+    let on_dark = |color: Color| {
+        board.pieces(Piece::Bishop) & board.color_combined(color) & DARK_SQUARES != EMPTY
+    };
+    on_dark(Color::White) != on_dark(Color::Black)
+}
+
+// The a-, b-, c- and d-files
+fn queenside() -> BitBoard {
+    [File::A, File::B, File::C, File::D]
+        .into_iter()
+        .fold(EMPTY, |files, file| files | get_file(file))
+}
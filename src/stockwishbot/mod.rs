@@ -1,7 +1,69 @@
+mod attacks;
+mod bitbase;
+mod book;
 mod cache;
+mod endgame;
+mod eval_cache;
 mod evaluation;
+mod evaluator;
+mod incremental;
+mod king_safety;
+mod material;
 mod move_ordering;
+mod nnue;
+mod pawn_structure;
+mod ponder;
+mod skill;
 mod statistics;
 mod stockwish;
+mod symmetry;
+mod tablebase;
+mod threats;
+mod trace;
+mod weights;
+pub use bitbase::Bitbases;
+pub use bitbase::Wdl;
+pub use bitbase::MAX_BITBASE_PIECES;
+pub use book::decode_move;
+pub use book::encode_move;
+pub use book::polyglot_key;
+pub use book::BookEntry;
+pub use book::BookOptions;
+pub use book::BookSelection;
+pub use book::OpeningBook;
+pub use evaluation::evaluate_with_trace;
+pub use evaluation::quiet_position;
+pub use evaluation::raw_board_score;
+pub use evaluation::rule_score;
+pub use evaluation::MobilityWeights;
+pub use evaluation::PieceWeights;
+pub use evaluation::TaperedScore;
+pub use evaluation::CENTIPAWN;
+pub use evaluator::DefaultEvaluator;
+pub use evaluator::Evaluator;
+pub use evaluator::MaterialEvaluator;
+pub use incremental::IncrementalEval;
+pub use king_safety::KingSafetyWeights;
+pub use material::ImbalanceWeights;
+pub use material::ScaleWeights;
+pub use nnue::Network;
+pub use pawn_structure::PawnWeights;
+pub use ponder::PonderHandle;
+pub use skill::OpeningVariety;
+pub use skill::Skill;
+pub use skill::MAX_SKILL_LEVEL;
+pub use stockwish::AnalysisLine;
 pub use stockwish::Calibration;
+pub use stockwish::DrawKind;
+pub use stockwish::EvaluationMode;
+pub use stockwish::SearchControl;
 pub use stockwish::StockWish;
+pub use symmetry::check_symmetry;
+pub use symmetry::color_flipped;
+pub use symmetry::Asymmetry;
+pub use tablebase::Tablebase;
+pub use threats::ThreatWeights;
+pub use trace::EvalTrace;
+pub use weights::EvalWeights;
+pub use weights::PieceSquareTables;
+pub use weights::PieceValues;
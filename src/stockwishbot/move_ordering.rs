@@ -1,8 +1,8 @@
-use chess::{BitBoard, Board, ChessMove, MoveGen, EMPTY};
+use chess::{BitBoard, Board, ChessMove, MoveGen, Piece, EMPTY};
 use itertools::Itertools;
 
 use super::cache::TopTargets;
-use super::evaluation::piece_value;
+use super::weights::PieceValues;
 //
 // A better move order for iteration, hitting potentially high-value moves earlier
 //
@@ -18,11 +18,17 @@ enum MoveCategory {
     Cached(i32),
 }
 
+// Move ordering only needs a rough idea of what pieces are worth, so it uses the default values
+// rather than those of the weights.
+fn ordering_value(piece: Option<Piece>) -> i32 {
+    piece.map_or(0, |piece| PieceValues::default().of(piece))
+}
+
 fn mvv_lva(board: &Board, chess_move: &ChessMove) -> i32 {
     // The tentative score of a capture, as value of victim minus value of attacker
     let victim = board.piece_on(chess_move.get_dest());
     let attacker = board.piece_on(chess_move.get_source());
-    piece_value(victim) - piece_value(attacker)
+    ordering_value(victim) - ordering_value(attacker)
 }
 
 fn move_score(
@@ -39,7 +45,7 @@ fn move_score(
     }
     // Promotions are next in line
     if let Some(promotion_piece) = a.get_promotion() {
-        return MoveCategory::Promotion(piece_value(Some(promotion_piece)));
+        return MoveCategory::Promotion(ordering_value(Some(promotion_piece)));
     }
     // Captures are ranked after MVV-LVA
     if other_players_pieces & BitBoard::from_square(a.get_dest()) != BitBoard::new(0) {
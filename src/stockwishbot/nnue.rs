@@ -0,0 +1,211 @@
+// A small efficiently updatable neural network (NNUE), as an alternative to the classical evaluation.
+// The 768 inputs are one per piece type, colour and square, seen from each player's perspective.
+// They feed a hidden layer of N neurons per perspective, whose values (the accumulator) are updated
+// move by move. Both halves pass through a clipped ReLU into a single output neuron, with the half of
+// the player to move first. Everything is done in integers, so the results are the same on every CPU.
+use chess::Board;
+use chess::Color;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::ALL_PIECES;
+use std::fs;
+use std::path::Path;
+
+use super::evaluation::CENTIPAWN;
+
+pub const NETWORK_INPUTS: usize = 768;
+// The accumulator is a fixed-size array, so the search can copy it from move to move without
+// allocating. Networks may use any hidden layer size up to this one.
+pub const MAX_HIDDEN_SIZE: usize = 256;
+// The hidden layer is clipped to 0..=ACTIVATION_RANGE, and the output weights are scaled by
+// OUTPUT_WEIGHT_SCALE. Together with OUTPUT_SCALE this turns the output into centipawns.
+const ACTIVATION_RANGE: i32 = 255;
+const OUTPUT_WEIGHT_SCALE: i32 = 64;
+const OUTPUT_SCALE: i32 = 400;
+
+// The file starts with MAGIC, a little-endian u32 format version and a u32 hidden layer size N.
+// Then follow, all little-endian: the input weights as 768 × N i16s (all N weights of the first
+// input first), the N i16 hidden biases, the 2N i16 output weights and a single i32 output bias.
+const MAGIC: &[u8; 4] = b"SWNN";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    hidden: usize,
+    input_weights: Vec<i16>,
+    hidden_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Network {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            anyhow::bail!("Not a StockWish network file");
+        }
+        let version = reader.u32()?;
+        if version != FORMAT_VERSION {
+            anyhow::bail!("Unsupported network format version {}", version);
+        }
+        let hidden = reader.u32()? as usize;
+        if hidden > MAX_HIDDEN_SIZE {
+            anyhow::bail!(
+                "A hidden layer of {} neurons is larger than the maximum of {}",
+                hidden,
+                MAX_HIDDEN_SIZE
+            );
+        }
+        let network = Self {
+            hidden,
+            input_weights: reader.i16s(NETWORK_INPUTS * hidden)?,
+            hidden_biases: reader.i16s(hidden)?,
+            output_weights: reader.i16s(2 * hidden)?,
+            output_bias: reader.i32()?,
+        };
+        if !reader.bytes.is_empty() {
+            anyhow::bail!("{} unexpected bytes after the network", reader.bytes.len());
+        }
+        Ok(network)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(FORMAT_VERSION.to_le_bytes());
+        bytes.extend((self.hidden as u32).to_le_bytes());
+        for value in self
+            .input_weights
+            .iter()
+            .chain(&self.hidden_biases)
+            .chain(&self.output_weights)
+        {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    // The hidden layer computed from scratch
+    pub fn accumulator(&self, board: &Board) -> Accumulator {
+        let mut biases = [0; MAX_HIDDEN_SIZE];
+        biases[..self.hidden].copy_from_slice(&self.hidden_biases);
+        let mut accumulator = Accumulator {
+            values: [biases, biases],
+        };
+        for color in ALL_COLORS {
+            for piece in ALL_PIECES {
+                for square in board.pieces(piece) & board.color_combined(color) {
+                    self.update(&mut accumulator, piece, color, square, 1);
+                }
+            }
+        }
+        accumulator
+    }
+
+    // Adds (sign 1) or removes (sign -1) a piece from both halves of the accumulator
+    pub fn update(
+        &self,
+        accumulator: &mut Accumulator,
+        piece: Piece,
+        color: Color,
+        square: Square,
+        sign: i16,
+    ) {
+        for perspective in ALL_COLORS {
+            let input = input_index(perspective, piece, color, square);
+            let weights = &self.input_weights[input * self.hidden..(input + 1) * self.hidden];
+            let values = &mut accumulator.values[perspective.to_index()][..self.hidden];
+            for (value, weight) in values.iter_mut().zip(weights) {
+                *value = value.wrapping_add(sign.wrapping_mul(*weight));
+            }
+        }
+    }
+
+    // In evaluation units, from the point-of-view of the player to move
+    pub fn evaluate(&self, accumulator: &Accumulator, side_to_move: Color) -> i32 {
+        let (own, other) = self.output_weights.split_at(self.hidden);
+        let perspectives = [
+            (
+                &accumulator.values[side_to_move.to_index()][..self.hidden],
+                own,
+            ),
+            (
+                &accumulator.values[(!side_to_move).to_index()][..self.hidden],
+                other,
+            ),
+        ];
+        let mut sum = 0i64;
+        for (values, weights) in perspectives {
+            for (&value, &weight) in values.iter().zip(weights) {
+                let activation = (value as i32).clamp(0, ACTIVATION_RANGE);
+                sum += (activation * weight as i32) as i64;
+            }
+        }
+        let output = (sum + self.output_bias as i64) * OUTPUT_SCALE as i64
+            / (ACTIVATION_RANGE * OUTPUT_WEIGHT_SCALE) as i64;
+        output as i32 * CENTIPAWN
+    }
+}
+
+// The hidden layer before activation, indexed by the Color::to_index() of the perspective. Only the
+// first hidden_size() values of each half are used, the rest stay 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Accumulator {
+    values: [[i16; MAX_HIDDEN_SIZE]; 2],
+}
+
+// Each perspective sees its own pieces as the first 384 inputs, with the board flipped for black,
+// so the network can share what it knows between the players.
+fn input_index(perspective: Color, piece: Piece, color: Color, square: Square) -> usize {
+    let side = if color == perspective { 0 } else { 384 };
+    let square = match perspective {
+        Color::White => square.to_index(),
+        Color::Black => square.to_index() ^ 56,
+    };
+    side + piece.to_index() * 64 + square
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        if self.bytes.len() < count {
+            anyhow::bail!("The network file ends too early");
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn i16s(&mut self, count: usize) -> anyhow::Result<Vec<i16>> {
+        let bytes = self.take(2 * count)?;
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect())
+    }
+}
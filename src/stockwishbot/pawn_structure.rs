@@ -0,0 +1,404 @@
+// Evaluation of the pawn structure of both players. The terms which only depend on the pawns
+// are cached in a pawn hash table, since the pawn structure rarely changes during a search.
+use chess::get_adjacent_files;
+use chess::get_file;
+use chess::get_pawn_attacks;
+use chess::BitBoard;
+use chess::Board;
+use chess::Color;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::EMPTY;
+use serde::Deserialize;
+use serde::Serialize;
+use std::cell::RefCell;
+
+use super::evaluation::tapered_table;
+use super::evaluation::TaperedScore;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PawnWeights {
+    pub doubled: TaperedScore,
+    pub isolated: TaperedScore,
+    pub backward: TaperedScore,
+    // Indexed by the rank of the pawn, as seen from its own side of the board
+    pub connected: [TaperedScore; 8],
+    pub passed: [TaperedScore; 8],
+    // Extra endgame bonus for a passed pawn with nothing at all in front of it
+    pub passed_free_path: [i32; 8],
+    // Endgame bonus per square of king distance to the square in front of a passed pawn.
+    // The enemy king should be far away, and our own king close by.
+    pub passed_enemy_king_distance: [i32; 8],
+    pub passed_own_king_distance: [i32; 8],
+}
+
+impl Default for PawnWeights {
+    fn default() -> Self {
+        Self {
+            doubled: TaperedScore::new(-10, -20),
+            isolated: TaperedScore::new(-10, -15),
+            backward: TaperedScore::new(-8, -10),
+            connected: tapered_table([0, 3, 5, 8, 15, 25, 40, 0], [0, 0, 2, 5, 10, 20, 30, 0]),
+            passed: tapered_table(
+                [0, 5, 10, 15, 25, 40, 60, 0],
+                [0, 10, 20, 35, 60, 90, 130, 0],
+            ),
+            passed_free_path: [0, 0, 0, 5, 10, 20, 35, 0],
+            passed_enemy_king_distance: [0, 0, 0, 3, 6, 10, 15, 0],
+            passed_own_king_distance: [0, 0, 0, -1, -3, -5, -7, 0],
+        }
+    }
+}
+
+// Indexed by Color::to_index(), each from that player's point-of-view.
+pub fn pawn_structure(board: &Board, weights: &PawnWeights) -> [TaperedScore; 2] {
+    let entry = probe_pawn_table(board, weights);
+    ALL_COLORS.map(|color| {
+        let i = color.to_index();
+        entry.score[i] + passed_pawn_surroundings(board, weights, color, entry.passed[i])
+    })
+}
+
+//
+// The pawn hash table
+//
+
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+#[derive(Clone, Copy)]
+struct PawnEntry {
+    key: u64,
+    // Both indexed by Color::to_index()
+    score: [TaperedScore; 2],
+    passed: [BitBoard; 2],
+}
+
+impl Default for PawnEntry {
+    fn default() -> Self {
+        Self {
+            key: 0,
+            score: [TaperedScore::default(); 2],
+            passed: [EMPTY; 2],
+        }
+    }
+}
+
+// The entries are only valid for the weights they were computed with.
+struct PawnTable {
+    weights: PawnWeights,
+    entries: Vec<PawnEntry>,
+}
+
+thread_local! {
+    static PAWN_TABLE: RefCell<PawnTable> = RefCell::new(PawnTable {
+        weights: PawnWeights::default(),
+        entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE],
+    });
+}
+
+fn probe_pawn_table(board: &Board, weights: &PawnWeights) -> PawnEntry {
+    let key = pawn_key(board);
+    PAWN_TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        if table.weights != *weights {
+            table.weights = weights.clone();
+            table.entries.fill(PawnEntry::default());
+        }
+        let slot = &mut table.entries[(key as usize) % PAWN_TABLE_SIZE];
+        if slot.key != key {
+            // An empty slot has key 0, which is also the key of a board without pawns.
+            // Luckily, its default entry is also correct for a board without pawns.
+            *slot = evaluate_pawns(board, weights, key);
+        }
+        *slot
+    })
+}
+
+// Zobrist keys for pawns only, indexed by Color::to_index() and Square::to_index()
+const PAWN_ZOBRIST: [[u64; 64]; 2] = {
+    let mut table = [[0; 64]; 2];
+    let mut state: u64 = 0x5057_4E5F_4841_5348;
+    let mut i = 0;
+    while i < 128 {
+        // SplitMix64
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i / 64][i % 64] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+// The chess crate does not implement Board::get_pawn_hash, so we compute our own.
+pub fn pawn_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for color in ALL_COLORS {
+        for square in board.pieces(Piece::Pawn) & board.color_combined(color) {
+            key ^= PAWN_ZOBRIST[color.to_index()][square.to_index()];
+        }
+    }
+    key
+}
+
+//
+// The terms themselves
+//
+
+fn evaluate_pawns(board: &Board, weights: &PawnWeights, key: u64) -> PawnEntry {
+    let white_pawns = board.pieces(Piece::Pawn) & board.color_combined(Color::White);
+    let black_pawns = board.pieces(Piece::Pawn) & board.color_combined(Color::Black);
+    let (white_score, white_passed) = pawn_terms(weights, white_pawns, black_pawns, Color::White);
+    let (black_score, black_passed) = pawn_terms(weights, black_pawns, white_pawns, Color::Black);
+    PawnEntry {
+        key,
+        score: [white_score, black_score],
+        passed: [white_passed, black_passed],
+    }
+}
+
+fn pawn_terms(
+    weights: &PawnWeights,
+    own: BitBoard,
+    enemy: BitBoard,
+    color: Color,
+) -> (TaperedScore, BitBoard) {
+    let mut score = TaperedScore::default();
+    let mut passed = EMPTY;
+    let enemy_attacks = pawn_attacks(enemy, !color);
+    for square in own {
+        let file = square.get_file();
+        let rank = relative_rank(square, color);
+        let adjacent_own = own & get_adjacent_files(file);
+        let in_front = forward_ranks(square, color);
+        // Doubled pawns are counted once for every pawn behind another
+        if own & get_file(file) & in_front != EMPTY {
+            score += weights.doubled;
+        }
+        if adjacent_own == EMPTY {
+            score += weights.isolated;
+        } else if adjacent_own & !in_front == EMPTY {
+            // No pawn on the adjacent files can ever come to support it
+            if let Some(stop) = square.forward(color) {
+                if enemy_attacks & BitBoard::from_square(stop) != EMPTY {
+                    score += weights.backward;
+                }
+            }
+        }
+        let supported = get_pawn_attacks(square, !color, own) != EMPTY;
+        let phalanx = adjacent_own & same_rank(square) != EMPTY;
+        if supported || phalanx {
+            score += weights.connected[rank];
+        }
+        let front_span = in_front & (get_file(file) | get_adjacent_files(file));
+        if enemy & front_span == EMPTY && own & get_file(file) & in_front == EMPTY {
+            score += weights.passed[rank];
+            passed |= BitBoard::from_square(square);
+        }
+    }
+    (score, passed)
+}
+
+// Terms for passed pawns which depend on more than the pawns, and can therefore not be cached.
+fn passed_pawn_surroundings(
+    board: &Board,
+    weights: &PawnWeights,
+    color: Color,
+    passed: BitBoard,
+) -> TaperedScore {
+    let mut endgame = 0;
+    let own_king = board.king_square(color);
+    let enemy_king = board.king_square(!color);
+    for square in passed {
+        let rank = relative_rank(square, color);
+        let path = forward_ranks(square, color) & get_file(square.get_file());
+        if path & board.combined() == EMPTY {
+            endgame += weights.passed_free_path[rank];
+        }
+        if let Some(stop) = square.forward(color) {
+            endgame += weights.passed_enemy_king_distance[rank] * distance(enemy_king, stop)
+                + weights.passed_own_king_distance[rank] * distance(own_king, stop);
+        }
+    }
+    TaperedScore::new(0, endgame)
+}
+
+//
+// Bitboard helpers
+//
+
+pub fn relative_rank(square: Square, color: Color) -> usize {
+    match color {
+        Color::White => square.get_rank().to_index(),
+        Color::Black => 7 - square.get_rank().to_index(),
+    }
+}
+
+// All squares on the ranks in front of the square, as seen by the given player
+pub fn forward_ranks(square: Square, color: Color) -> BitBoard {
+    let rank = square.get_rank().to_index() as u32;
+    match color {
+        Color::White => BitBoard(u64::MAX.checked_shl(8 * (rank + 1)).unwrap_or(0)),
+        Color::Black => BitBoard((1u64 << (8 * rank)) - 1),
+    }
+}
+
+fn same_rank(square: Square) -> BitBoard {
+    chess::get_rank(square.get_rank())
+}
+
+pub fn pawn_attacks(pawns: BitBoard, color: Color) -> BitBoard {
+    pawns.fold(EMPTY, |attacks, square| {
+        attacks | get_pawn_attacks(square, color, !EMPTY)
+    })
+}
+
+// Number of king moves between two squares
+pub fn distance(a: Square, b: Square) -> i32 {
+    let files = (a.get_file().to_index() as i32 - b.get_file().to_index() as i32).abs();
+    let ranks = (a.get_rank().to_index() as i32 - b.get_rank().to_index() as i32).abs();
+    std::cmp::max(files, ranks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // Weights which only count the pawns the given term applies to, one midgame point each
+    fn counting(term: impl Fn(&mut PawnWeights)) -> PawnWeights {
+        let zero = TaperedScore::default();
+        let mut weights = PawnWeights {
+            doubled: zero,
+            isolated: zero,
+            backward: zero,
+            connected: [zero; 8],
+            passed: [zero; 8],
+            passed_free_path: [0; 8],
+            passed_enemy_king_distance: [0; 8],
+            passed_own_king_distance: [0; 8],
+        };
+        term(&mut weights);
+        weights
+    }
+
+    fn from_fen(fen: &str) -> Board {
+        Board::from_str(fen).expect("Invalid FEN")
+    }
+
+    // How many of white's pawns the weights count, and which of them are passed
+    fn white_terms(fen: &str, weights: &PawnWeights) -> (i32, BitBoard) {
+        let board = from_fen(fen);
+        let entry = evaluate_pawns(&board, weights, pawn_key(&board));
+        let white = Color::White.to_index();
+        (entry.score[white].midgame, entry.passed[white])
+    }
+
+    fn cached_entry(key: u64) -> PawnEntry {
+        PAWN_TABLE.with(|table| table.borrow().entries[(key as usize) % PAWN_TABLE_SIZE])
+    }
+
+    #[test]
+    fn doubled_pawns() {
+        let weights = counting(|w| w.doubled = TaperedScore::new(1, 0));
+        assert_eq!(
+            white_terms("4k3/8/8/8/4P3/4P3/8/4K3 w - - 0 1", &weights).0,
+            1
+        );
+        assert_eq!(
+            white_terms("4k3/8/8/8/4P3/4P3/4P3/4K3 w - - 0 1", &weights).0,
+            2
+        );
+        assert_eq!(
+            white_terms("4k3/8/8/8/4P3/3P4/8/4K3 w - - 0 1", &weights).0,
+            0
+        );
+    }
+
+    #[test]
+    fn isolated_pawns() {
+        let weights = counting(|w| w.isolated = TaperedScore::new(1, 0));
+        assert_eq!(
+            white_terms("4k3/8/8/8/8/8/P1P1P3/4K3 w - - 0 1", &weights).0,
+            3
+        );
+        assert_eq!(
+            white_terms("4k3/8/8/8/8/8/PP2P3/4K3 w - - 0 1", &weights).0,
+            1
+        );
+    }
+
+    #[test]
+    fn backward_pawns() {
+        let weights = counting(|w| w.backward = TaperedScore::new(1, 0));
+        // The pawn on e3 is behind its neighbour, and cannot advance past the pawn on f5
+        assert_eq!(
+            white_terms("4k3/8/8/5p2/3P4/4P3/8/4K3 w - - 0 1", &weights).0,
+            1
+        );
+        assert_eq!(
+            white_terms("4k3/8/8/8/3P4/4P3/8/4K3 w - - 0 1", &weights).0,
+            0
+        );
+        // Its neighbour can still come to support it
+        assert_eq!(
+            white_terms("4k3/8/8/5p2/8/4P3/3P4/4K3 w - - 0 1", &weights).0,
+            0
+        );
+    }
+
+    #[test]
+    fn passed_pawns() {
+        let weights = counting(|w| w.passed = [TaperedScore::new(1, 0); 8]);
+        let a5 = BitBoard::from_square(Square::A5);
+        // A pawn on an adjacent file in front stops it
+        assert_eq!(
+            white_terms("4k3/1p6/8/P7/8/8/8/4K3 w - - 0 1", &weights),
+            (0, EMPTY)
+        );
+        assert_eq!(
+            white_terms("4k3/2p5/8/P7/8/8/8/4K3 w - - 0 1", &weights),
+            (1, a5)
+        );
+        // Of doubled pawns only the front one is passed
+        assert_eq!(
+            white_terms("4k3/8/8/P7/P7/8/8/4K3 w - - 0 1", &weights),
+            (1, a5)
+        );
+    }
+
+    #[test]
+    fn pawn_table_hits_match_a_fresh_evaluation() {
+        let weights = PawnWeights::default();
+        let board = from_fen("r1bqkb1r/pp3ppp/2n1pn2/2pp4/3P4/2PBPN2/PP3PPP/RNBQK2R w KQkq - 0 1");
+        let key = pawn_key(&board);
+        let fresh = evaluate_pawns(&board, &weights, key);
+        let first = probe_pawn_table(&board, &weights);
+        assert_eq!(cached_entry(key).key, key);
+        let hit = probe_pawn_table(&board, &weights);
+        for entry in [first, hit] {
+            assert_eq!(entry.score, fresh.score);
+            assert_eq!(entry.passed, fresh.passed);
+        }
+    }
+
+    #[test]
+    fn pawn_table_is_cleared_when_the_weights_change() {
+        let board = from_fen("4k3/8/8/8/4P3/4P3/8/4K3 w - - 0 1");
+        let other = from_fen("4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1");
+        let key = pawn_key(&board);
+        probe_pawn_table(&board, &PawnWeights::default());
+        assert_eq!(cached_entry(key).key, key);
+        let weights = PawnWeights {
+            doubled: TaperedScore::new(-50, -50),
+            ..PawnWeights::default()
+        };
+        // Probing any board with other weights forgets every entry of the old weights
+        probe_pawn_table(&other, &weights);
+        assert_eq!(cached_entry(key).key, 0);
+        let entry = probe_pawn_table(&board, &weights);
+        assert_eq!(entry.score, evaluate_pawns(&board, &weights, key).score);
+    }
+}
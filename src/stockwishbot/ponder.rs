@@ -0,0 +1,82 @@
+use chess::ChessMove;
+use chess::Game;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::stockwish::AnalysisLine;
+use super::stockwish::SearchControl;
+use super::StockWish;
+
+//
+// Pondering: searching the position after the opponent's expected reply, while the opponent is thinking.
+//
+
+pub struct PonderHandle {
+    ponder_move: ChessMove,
+    control: SearchControl,
+    // Only taken when the search is joined
+    handle: Option<JoinHandle<(StockWish, Vec<AnalysisLine>)>>,
+}
+
+impl StockWish {
+    // Starts searching the position arising after our own move has been played on the game,
+    // and the opponent has replied with ponder_move. The search runs until it reaches full depth,
+    // or until ponder_hit or ponder_miss is called on the returned handle.
+    pub fn ponder(self, game: Game, ponder_move: ChessMove) -> PonderHandle {
+        let control = self.control();
+        let mut stockwish = self;
+        let mut pondered_game = game;
+        pondered_game.make_move(ponder_move);
+        let handle = thread::spawn(move || {
+            let lines = stockwish.analyse_iterative_deepening(pondered_game);
+            (stockwish, lines)
+        });
+        PonderHandle {
+            ponder_move,
+            control,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl PonderHandle {
+    pub fn ponder_move(&self) -> ChessMove {
+        self.ponder_move
+    }
+
+    // The opponent played the expected move. The ponder search becomes a normal search
+    // which stops once time_limit has passed from now, and its lines are returned.
+    pub fn ponder_hit(mut self, time_limit: Duration) -> (StockWish, Vec<AnalysisLine>) {
+        self.control.set_deadline(Instant::now() + time_limit);
+        let (mut stockwish, lines) = self.join();
+        stockwish.reset_control();
+        (stockwish, lines)
+    }
+
+    // The opponent played something else. The ponder search is discarded, but the cache
+    // it has warmed up is kept for the real search.
+    pub fn ponder_miss(mut self) -> StockWish {
+        self.control.stop();
+        let (mut stockwish, _) = self.join();
+        stockwish.reset_control();
+        stockwish
+    }
+
+    fn join(&mut self) -> (StockWish, Vec<AnalysisLine>) {
+        self.handle
+            .take()
+            .expect("Ponder search already joined")
+            .join()
+            .expect("Ponder search panicked")
+    }
+}
+
+// A handle dropped without a ponder hit or miss stops its search, which would otherwise keep the
+// engine busy in the background until it reached full depth.
+impl Drop for PonderHandle {
+    fn drop(&mut self) {
+        self.control.stop();
+    }
+}
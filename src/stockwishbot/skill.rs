@@ -0,0 +1,149 @@
+use chess::Board;
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use super::evaluation::CENTIPAWN;
+use super::stockwish::AnalysisLine;
+
+//
+// Adjustable playing strength. A weaker StockWish searches less, sees a noisy evaluation,
+// and does not always play its best move.
+//
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+const MIN_ELO: i32 = 800;
+const MAX_ELO: i32 = 2400;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Skill {
+    // Same meaning as the depth given to StockWish::new. The smaller of the two is used.
+    pub max_depth: i32,
+    // Stop searching after this many nodes. The first depth is always completed.
+    pub max_nodes: Option<u64>,
+    // Leaf evaluations are shifted by up to this many centipawns in either direction.
+    pub eval_noise: i32,
+    // Root moves scoring within this many centipawns of the best move may be played instead.
+    pub selection_margin: i32,
+    // How many root moves are candidates for being played.
+    pub candidates: usize,
+    // Fixes the randomness, so games can be reproduced. Otherwise it is seeded from entropy.
+    pub seed: Option<u64>,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self::from_level(MAX_SKILL_LEVEL)
+    }
+}
+
+impl Skill {
+    // Level 0 is a beginner, and MAX_SKILL_LEVEL is full strength.
+    pub fn from_level(level: u8) -> Self {
+        let level = std::cmp::min(level, MAX_SKILL_LEVEL);
+        if level == MAX_SKILL_LEVEL {
+            return Self {
+                max_depth: i32::MAX,
+                max_nodes: None,
+                eval_noise: 0,
+                selection_margin: 0,
+                candidates: 1,
+                seed: None,
+            };
+        }
+        let weakness = (MAX_SKILL_LEVEL - level) as i32;
+        Self {
+            max_depth: 2 + level as i32 / 4,
+            max_nodes: Some(2_000 * (level as u64 + 1).pow(2)),
+            eval_noise: 8 * weakness,
+            selection_margin: 10 * weakness,
+            candidates: 4,
+            seed: None,
+        }
+    }
+
+    // A rough mapping from a target rating to a skill level.
+    pub fn from_elo(elo: i32) -> Self {
+        let clamped = elo.clamp(MIN_ELO, MAX_ELO);
+        let level = (clamped - MIN_ELO) * MAX_SKILL_LEVEL as i32 / (MAX_ELO - MIN_ELO);
+        Self::from_level(level as u8)
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn is_full_strength(&self) -> bool {
+        self.max_nodes.is_none()
+            && self.eval_noise == 0
+            && self.selection_margin == 0
+            && self.max_depth == i32::MAX
+    }
+
+    pub(super) fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+// Varies the opening by playing any root move within margin centipawns of the best move,
+// during the first plies of the game. The seed is logged, so a game can be reproduced.
+#[derive(Clone, Copy, Debug)]
+pub struct OpeningVariety {
+    pub plies: usize,
+    pub margin: i32,
+    pub candidates: usize,
+    pub seed: u64,
+}
+
+impl OpeningVariety {
+    // Sensible settings for a fresh game, seeded from entropy.
+    pub fn new_game() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            plies: 8,
+            margin: 30,
+            candidates: 4,
+            seed,
+        }
+    }
+}
+
+// Deterministic noise for a position, so transpositions agree on their (wrong) evaluation.
+pub(super) fn evaluation_noise(board: &Board, eval_noise: i32, seed: u64) -> i32 {
+    if eval_noise == 0 {
+        return 0;
+    }
+    let mixed = (board.get_hash() ^ seed).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+    let span = 2 * eval_noise as u64 + 1;
+    ((mixed % span) as i32 - eval_noise) * CENTIPAWN
+}
+
+// Picks one of the lines scoring within margin centipawns of the best line. Lines closer to the best
+// are more likely to be picked. The lines must be sorted best first.
+pub(super) fn weighted_choice<'a>(
+    lines: &'a [AnalysisLine],
+    margin: i32,
+    rng: &mut StdRng,
+) -> Option<&'a AnalysisLine> {
+    let best = lines.first()?.score as i64;
+    let margin = (margin * CENTIPAWN) as i64;
+    let candidates: Vec<&AnalysisLine> = lines
+        .iter()
+        .filter(|line| best - line.score as i64 <= margin)
+        .collect();
+    let weights = candidates
+        .iter()
+        .map(|line| margin + 1 - (best - line.score as i64));
+    let distribution = WeightedIndex::new(weights).ok()?;
+    Some(candidates[distribution.sample(rng)])
+}
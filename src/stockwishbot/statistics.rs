@@ -1,9 +1,12 @@
 use std::time::Instant;
 
+use super::eval_cache::EvalCache;
+
 // Simple struct to gather data about how well the chess bot performs.
 pub struct Statistics {
     start: Instant,
     iterations: i32,
+    tablebase_hits: i32,
 }
 
 impl Statistics {
@@ -11,6 +14,7 @@ impl Statistics {
         Self {
             start: Instant::now(),
             iterations: 0,
+            tablebase_hits: 0,
         }
     }
 
@@ -18,12 +22,20 @@ impl Statistics {
         self.iterations += 1;
     }
 
-    pub fn stop(self) {
+    pub fn tablebase_hit(&mut self) {
+        self.tablebase_hits += 1;
+    }
+
+    pub fn stop(self, eval_cache: &EvalCache) {
         let dur = Instant::now() - self.start;
         println!(
-            "Run finished. Considered {} positions in {} seconds",
+            "Run finished. Considered {} positions in {} seconds, with {} tablebase hits. Evaluation cache: {} of {} probes hit ({:.1}%)",
             self.iterations,
-            dur.as_secs_f32()
+            dur.as_secs_f32(),
+            self.tablebase_hits,
+            eval_cache.hits(),
+            eval_cache.probes(),
+            100.0 * eval_cache.hit_rate()
         )
     }
 }
@@ -1,18 +1,217 @@
+use chess::Action;
 use chess::Board;
+use chess::BoardStatus;
 use chess::ChessMove;
+use chess::Color;
 use chess::Game;
+use chess::Piece;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
+use super::bitbase::Bitbases;
+use super::book::BookOptions;
+use super::book::OpeningBook;
 use super::cache::insert_in_cache_if_better;
 use super::cache::SWCache;
 use super::cache::Score;
 use super::cache::TopTargets;
+use super::eval_cache::EvalCache;
 use super::evaluation::quiescent_board_score;
+use super::evaluation::QuiescenceContext;
+use super::evaluation::CENTIPAWN;
+use super::evaluator::DefaultEvaluator;
+use super::evaluator::Evaluator;
+use super::incremental::IncrementalEval;
 use super::move_ordering::generate_move_order;
+use super::nnue::Network;
+use super::skill::evaluation_noise;
+use super::skill::weighted_choice;
+use super::skill::OpeningVariety;
+use super::skill::Skill;
 use super::statistics::Statistics;
+use super::tablebase::Tablebase;
+use super::weights::EvalWeights;
 
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone)]
 pub struct Calibration {
-    pub positional_weight: i32,
+    // How many centipawns the root side gives up by drawing. Negative contempt makes it welcome draws.
+    pub contempt: i32,
+    // What each kind of draw is worth to the root side in centipawns, before contempt is applied.
+    pub repetition_draw: i32,
+    pub fifty_move_draw: i32,
+    pub insufficient_material_draw: i32,
+    // Every parameter of the evaluation itself
+    pub weights: EvalWeights,
+    pub evaluation: EvaluationMode,
+    // Generated win/draw/loss tables for endings with few pieces
+    pub bitbases: Option<Arc<Bitbases>>,
+}
+
+// Which evaluation scores the positions that are not decided by a rule or a known endgame.
+#[derive(Clone, Debug, Default)]
+pub enum EvaluationMode {
+    // The hand-written terms, with the weights of the calibration
+    #[default]
+    Classical,
+    Network(Arc<Network>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawKind {
+    Stalemate,
+    Repetition,
+    FiftyMove,
+    InsufficientMaterial,
+    // A draw according to the endgame tablebases or bitbases
+    Tablebase,
+}
+
+impl Calibration {
+    // The score of a draw from the point-of-view of the player who's turn it is. The root side is
+    // the side to move at the root of the search. Without one there is nobody to have contempt, so
+    // a draw is simply 0.
+    pub fn draw_score(&self, kind: DrawKind, side_to_move: Color, root_side: Option<Color>) -> i32 {
+        let base = match kind {
+            DrawKind::Stalemate | DrawKind::Tablebase => 0,
+            DrawKind::Repetition => self.repetition_draw,
+            DrawKind::FiftyMove => self.fifty_move_draw,
+            DrawKind::InsufficientMaterial => self.insufficient_material_draw,
+        };
+        let for_root_side = (base - self.contempt) * CENTIPAWN;
+        match root_side {
+            Some(root_side) if root_side == side_to_move => for_root_side,
+            Some(_) => -for_root_side,
+            None => 0,
+        }
+    }
+}
+
+// A single line of analysis. The score is from the point-of-view of the player who's turn it is,
+// and the principal variation starts with the candidate move itself.
+#[derive(Clone, Debug)]
+pub struct AnalysisLine {
+    pub score: i32,
+    pub depth: i32,
+    pub pv: Vec<ChessMove>,
+}
+
+impl AnalysisLine {
+    // The reply we expect from the opponent, which is what we ponder on.
+    pub fn ponder_move(&self) -> Option<ChessMove> {
+        self.pv.get(1).copied()
+    }
+}
+
+// Lets a running search be stopped, either right away, once a deadline has passed, or after a number of nodes.
+// Clones share the same state, so a clone can be handed to whoever needs to stop the search.
+#[derive(Clone)]
+pub struct SearchControl {
+    stop: Arc<AtomicBool>,
+    // The deadline in nanoseconds since start, so it can be read on every node without a lock
+    start: Instant,
+    deadline: Arc<AtomicU64>,
+    nodes: Arc<AtomicU64>,
+    // Zero means no limit
+    node_limit: Arc<AtomicU64>,
+}
+
+const NO_DEADLINE: u64 = u64::MAX;
+// Reading the clock is slower than searching a node, so it is only read once every so many nodes.
+const CLOCK_CHECK_NODES: u64 = 1024;
+
+impl Default for SearchControl {
+    fn default() -> Self {
+        Self {
+            stop: Arc::default(),
+            start: Instant::now(),
+            deadline: Arc::new(AtomicU64::new(NO_DEADLINE)),
+            nodes: Arc::default(),
+            node_limit: Arc::default(),
+        }
+    }
+}
+
+impl SearchControl {
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set_deadline(&self, deadline: Instant) {
+        let nanos = deadline.saturating_duration_since(self.start).as_nanos();
+        self.deadline.store(
+            nanos.min((NO_DEADLINE - 1) as u128) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    // Also restarts the node count.
+    pub fn set_node_limit(&self, node_limit: u64) {
+        self.nodes.store(0, Ordering::Relaxed);
+        self.node_limit.store(node_limit, Ordering::Relaxed);
+    }
+
+    fn count_node(&self) {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn should_stop(&self) -> bool {
+        if self.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        let node_limit = self.node_limit.load(Ordering::Relaxed);
+        if node_limit != 0 && self.nodes.load(Ordering::Relaxed) >= node_limit {
+            return true;
+        }
+        let deadline = self.deadline.load(Ordering::Relaxed);
+        let nodes = self.nodes.load(Ordering::Relaxed);
+        if deadline == NO_DEADLINE || !nodes.is_multiple_of(CLOCK_CHECK_NODES) {
+            return false;
+        }
+        if self.start.elapsed().as_nanos() >= deadline as u128 {
+            // Later checks need not read the clock again
+            self.stop();
+            return true;
+        }
+        false
+    }
+}
+
+// A position on the way from the start of the game to the current search node.
+#[derive(Clone, Copy)]
+struct PathEntry {
+    hash: u64,
+    // Plies since the last capture or pawn move
+    halfmove_clock: u32,
+    // Whether this position was reached by a null move, which resets the halfmove clock without
+    // any capture or pawn move having been made
+    is_null: bool,
+}
+
+// Everything a running search needs, apart from the position and the alpha-beta window.
+struct SearchContext<'a> {
+    stats: &'a mut Statistics,
+    cache: &'a mut SWCache,
+    eval_cache: &'a mut EvalCache,
+    calibration: &'a Calibration,
+    // The side to move at the root, from whose point-of-view draws are scored
+    root_side: Color,
+    evaluator: &'a dyn Evaluator,
+    tablebase: Option<&'a Tablebase>,
+    control: &'a SearchControl,
+    // Centipawns of noise added to leaf evaluations, for weaker play.
+    eval_noise: i32,
+    noise_seed: u64,
+    // The game history followed by the current search line, ending with the current node.
+    path: Vec<PathEntry>,
+    // The incremental evaluation of every node on the current search line, ending with the current node.
+    evals: Vec<IncrementalEval>,
 }
 
 // TODO: Should not derive clone, since it now owns a lot of data.
@@ -20,100 +219,326 @@ pub struct Calibration {
 pub struct StockWish {
     depth: i32,
     cache: SWCache,
+    eval_cache: EvalCache,
     calibration: Calibration,
+    evaluator: Arc<dyn Evaluator>,
+    tablebase: Option<Arc<Tablebase>>,
+    // Number of ranked lines to search for (MultiPV). The best line always comes first.
+    multi_pv: usize,
+    control: SearchControl,
+    skill: Skill,
+    rng: StdRng,
+    noise_seed: u64,
+    opening_variety: Option<(OpeningVariety, StdRng)>,
+    book: Option<(Arc<OpeningBook>, BookOptions, StdRng)>,
+    // Number of plies played in the game currently being analysed
+    root_ply: usize,
+    // Positions of the game currently being analysed, for detecting repetitions and the fifty-move rule
+    history: Vec<PathEntry>,
 }
 
 impl Default for StockWish {
     fn default() -> Self {
-        Self {
-            depth: 8,
-            cache: SWCache::new(10_000_000),
-            calibration: Calibration::default(),
-        }
+        Self::new(8, Calibration::default())
     }
 }
 
 impl StockWish {
     pub fn new(depth: i32, calibration: Calibration) -> Self {
+        let skill = Skill::default();
+        let mut rng = skill.rng();
         Self {
             depth,
             cache: SWCache::new(10_000_000),
+            eval_cache: EvalCache::default(),
             calibration,
+            evaluator: Arc::new(DefaultEvaluator),
+            tablebase: None,
+            multi_pv: 1,
+            control: SearchControl::default(),
+            skill,
+            noise_seed: rng.gen(),
+            rng,
+            opening_variety: None,
+            book: None,
+            root_ply: 0,
+            history: vec![],
         }
     }
 
+    pub fn set_opening_variety(&mut self, variety: Option<OpeningVariety>) {
+        if let Some(v) = variety {
+            println!(
+                "Opening variety for {} plies within {} centipawns, seed {}",
+                v.plies, v.margin, v.seed
+            );
+        }
+        self.opening_variety = variety.map(|v| (v, StdRng::seed_from_u64(v.seed)));
+    }
+
+    // An opening book to play from before searching, or None to always search.
+    pub fn set_book(&mut self, book: Option<Arc<OpeningBook>>, options: BookOptions) {
+        self.book = book.map(|book| (book, options, StdRng::seed_from_u64(options.seed)));
+    }
+
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+        self.rng = skill.rng();
+        self.noise_seed = self.rng.gen();
+    }
+
+    // Replaces the evaluation used by the search. The calibration is still passed to it. Scores of
+    // the old evaluation are forgotten, including those in the transposition table.
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+        self.eval_cache.clear();
+        self.cache = SWCache::new(10_000_000);
+    }
+
+    // Endgame tablebases to probe during the search, or None to rely on the evaluation alone.
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = std::cmp::max(lines, 1);
+    }
+
+    // A handle which can stop the current or next search of this StockWish.
+    pub fn control(&self) -> SearchControl {
+        self.control.clone()
+    }
+
+    // Forget any stop request or deadline, so the next search runs normally.
+    pub(super) fn reset_control(&mut self) {
+        self.control = SearchControl::default();
+    }
+
     //
     // Returns the best next move using iterative deepening.
     //
     pub fn best_next_move_iterative_deepening(&mut self, game: Game) -> Option<ChessMove> {
-        let mut best_move = None;
-        println!("--------------------");
-        for d in 1..self.depth {
-            best_move = self.root_search(game.clone(), d);
-            println!(
-                "Depth: {} ::: Best move is from {} to {}",
-                d,
-                best_move.unwrap().get_source().to_string(),
-                best_move.unwrap().get_dest().to_string()
-            );
+        let lines = self.analyse_iterative_deepening(game);
+        self.pick_line(&lines).map(|line| line.pv[0])
+    }
+
+    //
+    // Chooses which of the analysed lines to play. At full strength this is always the best line.
+    //
+    pub fn pick_line(&mut self, lines: &[AnalysisLine]) -> Option<AnalysisLine> {
+        let root_ply = self.root_ply;
+        if let Some((variety, variety_rng)) = self.active_opening_variety() {
+            let chosen = weighted_choice(lines, variety.margin, variety_rng).cloned();
+            if let Some(line) = &chosen {
+                println!(
+                    "Opening variety: playing {} at ply {}",
+                    line.pv[0], root_ply
+                );
+            }
+            return chosen;
+        }
+        if self.skill.selection_margin == 0 {
+            return lines.first().cloned();
+        }
+        weighted_choice(lines, self.skill.selection_margin, &mut self.rng).cloned()
+    }
+
+    fn book_move(&mut self, board: &Board) -> Option<ChessMove> {
+        match &mut self.book {
+            Some((book, options, book_rng)) if self.root_ply < options.max_plies => {
+                book.choose(board, options.selection, book_rng)
+            }
+            _ => None,
+        }
+    }
+
+    fn active_opening_variety(&mut self) -> Option<(OpeningVariety, &mut StdRng)> {
+        match &mut self.opening_variety {
+            Some((variety, variety_rng)) if self.root_ply < variety.plies => {
+                Some((*variety, variety_rng))
+            }
+            _ => None,
         }
-        // TODO: Principal variation encounters loops in the endgame??
-        // if let Some(first_move) = best_move {
-        //     println!(
-        //         "Principal variation is {:?}",
-        //         self.get_principal_variation(game.current_position(), first_move)
-        //             .iter()
-        //             .map(|m| m.to_string())
-        //             .reduce(|acc, m| acc + ", " + &m)
-        //             .unwrap()
-        //     );
-        // }
+    }
+
+    //
+    // Returns the best next move using iterative deepening, stopping after the given time.
+    //
+    pub fn best_next_move_timed(&mut self, game: Game, time_limit: Duration) -> Option<ChessMove> {
+        self.control.set_deadline(Instant::now() + time_limit);
+        let best_move = self.best_next_move_iterative_deepening(game);
+        self.reset_control();
         best_move
     }
 
-    fn root_search(&mut self, game: Game, depth: i32) -> Option<ChessMove> {
+    //
+    // Returns the analysed lines using iterative deepening, stopping after the given time.
+    //
+    pub fn analyse_timed(&mut self, game: Game, time_limit: Duration) -> Vec<AnalysisLine> {
+        self.control.set_deadline(Instant::now() + time_limit);
+        let lines = self.analyse_iterative_deepening(game);
+        self.reset_control();
+        lines
+    }
+
+    //
+    // Returns the multi_pv best lines using iterative deepening, best line first.
+    // If the search is stopped, the lines of the last completed depth are returned.
+    //
+    pub fn analyse_iterative_deepening(&mut self, game: Game) -> Vec<AnalysisLine> {
+        let mut lines = vec![];
+        println!("--------------------");
+        self.root_ply = game
+            .actions()
+            .iter()
+            .filter(|action| matches!(action, Action::MakeMove(_)))
+            .count();
+        self.history = game_history(&game);
+        if let Some(book_move) = self.book_move(&game.current_position()) {
+            println!("Book move {} at ply {}", book_move, self.root_ply);
+            return vec![AnalysisLine {
+                score: 0,
+                depth: 0,
+                pv: vec![book_move],
+            }];
+        }
+        // Also lifts the limit of a weaker skill which has since been replaced
+        self.control
+            .set_node_limit(self.skill.max_nodes.unwrap_or(0));
+        for d in 1..std::cmp::min(self.depth, self.skill.max_depth) {
+            // The first depth is always completed, so we have a move to play.
+            let control = if d == 1 {
+                SearchControl::default()
+            } else {
+                self.control.clone()
+            };
+            match self.multi_pv_search(&game.current_position(), d, &control) {
+                Some(completed_lines) => lines = completed_lines,
+                None => {
+                    println!("Search stopped at depth {}", d);
+                    break;
+                }
+            }
+            for (i, line) in lines.iter().enumerate() {
+                println!(
+                    "Depth: {} ::: Line {} ({}) is {}",
+                    d,
+                    i + 1,
+                    line.score,
+                    format_moves(&line.pv)
+                );
+            }
+        }
+        lines
+    }
+
+    fn multi_pv_search(
+        &mut self,
+        board: &Board,
+        depth: i32,
+        control: &SearchControl,
+    ) -> Option<Vec<AnalysisLine>> {
+        // Search the root once per line, each time excluding the moves of the previous lines.
+        // Returns None if the search was stopped before all lines were found.
+        let mut excluded: Vec<ChessMove> = vec![];
+        let mut lines = vec![];
+        // A weaker StockWish, or one varying its opening, needs a few candidates to choose between
+        let mut wanted_lines = std::cmp::max(self.multi_pv, self.skill.candidates);
+        if let Some((variety, _)) = self.active_opening_variety() {
+            wanted_lines = std::cmp::max(wanted_lines, variety.candidates);
+        }
+        while lines.len() < wanted_lines {
+            let result = self.root_search(board, depth, &excluded, control);
+            if control.should_stop() {
+                return None;
+            }
+            match result {
+                Some((chess_move, score)) => {
+                    excluded.push(chess_move);
+                    lines.push(AnalysisLine {
+                        score,
+                        depth,
+                        pv: self.get_principal_variation(*board, chess_move),
+                    });
+                }
+                None => break,
+            }
+        }
+        Some(lines)
+    }
+
+    fn root_search(
+        &mut self,
+        board: &Board,
+        depth: i32,
+        excluded: &[ChessMove],
+        control: &SearchControl,
+    ) -> Option<(ChessMove, i32)> {
         // A special alpha-beta search function for the root node
         let mut stats = Statistics::new();
-        let board = game.current_position();
+        self.eval_cache.reset_statistics();
+        let mut ctx = SearchContext {
+            stats: &mut stats,
+            cache: &mut self.cache,
+            eval_cache: &mut self.eval_cache,
+            calibration: &self.calibration,
+            root_side: board.side_to_move(),
+            evaluator: self.evaluator.as_ref(),
+            tablebase: self.tablebase.as_deref(),
+            control,
+            eval_noise: self.skill.eval_noise,
+            noise_seed: self.noise_seed,
+            path: self.history.clone(),
+            evals: vec![IncrementalEval::new(board, &self.calibration)],
+        };
         let mut alpha = i32::MIN + 1;
         let beta = i32::MAX;
         // Check cache and use for move-ordering
         let mut preferred_targets: Option<TopTargets> = None;
-        if let Some(cached_evaluation) = self.cache.get(&board.get_hash()) {
+        if let Some(cached_evaluation) = ctx.cache.get(&board.get_hash()) {
             preferred_targets = Some(cached_evaluation.targets.clone());
         }
+        // In the tables, only the moves which keep the best result are worth searching
+        let halfmove_clock = self.history.last().map_or(0, |entry| entry.halfmove_clock);
+        let tablebase_moves = ctx
+            .tablebase
+            .and_then(|tablebase| tablebase.root_moves(board, halfmove_clock));
+        if tablebase_moves.is_some() {
+            ctx.stats.tablebase_hit();
+        }
         // Prepare new cache entry
         let mut top_targets = TopTargets::new(3);
         // Time to search
         let mut best_move: Option<ChessMove> = None;
-        for chess_move in generate_move_order(&board, preferred_targets) {
-            let child_score: Score = -negamax_alpha_beta_cache(
-                &board.make_move_new(chess_move),
-                &mut stats,
-                depth,
-                &mut self.cache,
-                -beta,
-                -alpha,
-                self.calibration,
-            );
+        for chess_move in generate_move_order(board, preferred_targets) {
+            if excluded.contains(&chess_move) {
+                continue;
+            }
+            if let Some(moves) = &tablebase_moves {
+                if !moves.contains(&chess_move) {
+                    continue;
+                }
+            }
+            let child_score: Score =
+                -search_child(board, chess_move, &mut ctx, depth, -beta, -alpha);
+            if control.should_stop() {
+                break;
+            }
             let child_score_discounted = discount_checkmates(child_score.into());
             // Save if this is a good move
             top_targets.try_insert(child_score_discounted, &chess_move);
             // Check if this is the best move so far
-            if child_score_discounted > alpha {
-                alpha = child_score_discounted;
+            if child_score_discounted > alpha || best_move.is_none() {
+                alpha = std::cmp::max(alpha, child_score_discounted);
                 best_move = Some(chess_move);
             }
         }
-        insert_in_cache_if_better(
-            &board,
-            depth,
-            &Score::Exact(alpha),
-            top_targets,
-            &mut self.cache,
-        );
-        stats.stop();
-        best_move
+        // With moves excluded, or the search stopped, the root score is not the true score of the position.
+        if excluded.is_empty() && !control.should_stop() {
+            insert_in_cache_if_better(board, depth, &Score::Exact(alpha), top_targets, ctx.cache);
+        }
+        stats.stop(&self.eval_cache);
+        best_move.map(|m| (m, alpha))
     }
 
     // Reconstructs the principal variation from the cache
@@ -124,12 +549,19 @@ impl StockWish {
     ) -> Vec<ChessMove> {
         let mut pv = vec![first_move];
         let mut board = current_board.make_move_new(first_move);
+        // Repetitions would make us loop forever, so stop as soon as a position comes back.
+        let mut seen = vec![current_board.get_hash(), board.get_hash()];
         while let Some(cached) = self.cache.get(&board.get_hash()) {
-            if let Some(next_move) = cached.targets.ordered_moves().last() {
-                pv.push(*next_move);
-                board = board.make_move_new(*next_move);
-            } else {
-                break;
+            match cached.targets.ordered_moves().last() {
+                Some(next_move) if board.legal(*next_move) => {
+                    pv.push(*next_move);
+                    board = board.make_move_new(*next_move);
+                    if seen.contains(&board.get_hash()) {
+                        break;
+                    }
+                    seen.push(board.get_hash());
+                }
+                _ => break,
             }
         }
         pv
@@ -138,18 +570,31 @@ impl StockWish {
 
 fn negamax_alpha_beta_cache(
     board: &Board,
-    stats: &mut Statistics,
+    ctx: &mut SearchContext,
     remaining_depth: i32,
-    cache: &mut SWCache,
     _alpha: i32,
     _beta: i32,
-    calibration: Calibration,
 ) -> Score {
+    if ctx.control.should_stop() {
+        // The search has been stopped. Nothing returned from here on is used, or cached.
+        return Score::Exact(0);
+    }
+    ctx.control.count_node();
+    if let Some(draw) = draw_by_rule(board, &ctx.path) {
+        let score = ctx
+            .calibration
+            .draw_score(draw, board.side_to_move(), Some(ctx.root_side));
+        return Score::Exact(score);
+    }
+    if let Some(score) = probe_tablebase(board, ctx) {
+        ctx.stats.tablebase_hit();
+        return Score::Exact(score);
+    }
     let mut preferred_targets: Option<TopTargets> = None;
     let mut alpha = _alpha;
     let mut beta = _beta;
     // Check cache
-    if let Some(cached_evaluation) = cache.get(&board.get_hash()) {
+    if let Some(cached_evaluation) = ctx.cache.get(&board.get_hash()) {
         if cached_evaluation.depth >= remaining_depth {
             // If this move exists in the cache at a depth of at least remaining_depth, use this.
             // An exact score is amazing, then we use this directly. A lower bound or upper bound potentially narrows the alpha-beta range.
@@ -172,30 +617,49 @@ fn negamax_alpha_beta_cache(
     let valid_moves = generate_move_order(board, preferred_targets);
 
     if remaining_depth <= 0 || valid_moves.is_empty() {
-        stats.increment();
-        // This is a leaf or terminal node, so we evaluate. We don't cache these here, since quiescent_board_score does this for us.
+        ctx.stats.increment();
+        // This is a leaf or terminal node, so we evaluate, and cache the score at depth zero.
         //Score::Exact(raw_board_score(board, calibration)) // TODO: Change back to quiescent search
-        Score::Exact(quiescent_board_score(
-            board,
-            cache,
-            alpha,
-            beta,
-            calibration,
-        ))
+        let state = ctx
+            .evals
+            .last()
+            .expect("The root is always on the search line");
+        let mut quiescence = QuiescenceContext {
+            eval_cache: ctx.eval_cache,
+            evaluator: ctx.evaluator,
+            calibration: ctx.calibration,
+            root_side: ctx.root_side,
+        };
+        let score = quiescent_board_score(board, &mut quiescence, alpha, beta, state);
+        // The noise is cached along with the score, so the board scores the same when it is
+        // found in the cache later on.
+        // TODO: We could potentially find some good targets, but it would only involve captures,
+        // so probably not so useful for general tree search.
+        let score = add_noise(score, board, ctx);
+        insert_in_cache_if_better(board, 0, &score, TopTargets::new(0), ctx.cache);
+        Score::Exact(score.into())
     } else {
         // Not a leaf node. We must evaluate further down.
         // First up: Null-move pruning
         if let Some(null_moved_board) = null_move_pruning(board, remaining_depth) {
             // We do the null-check with a fresh cache, to not pollute the main cache.
+            // Repetitions through a null-move are not real, so the null-move resets the path.
+            ctx.path.push(PathEntry {
+                hash: null_moved_board.get_hash(),
+                halfmove_clock: 0,
+                is_null: true,
+            });
             let score = -negamax_alpha_beta_cache(
                 &null_moved_board,
-                stats,
+                ctx,
                 remaining_depth - 3,
-                cache,
                 -beta,
                 -beta + 1,
-                calibration,
             );
+            ctx.path.pop();
+            if ctx.control.should_stop() {
+                return Score::Exact(0);
+            }
             if i32::from(score) >= beta {
                 return Score::LowerBound(score.into());
             }
@@ -204,15 +668,11 @@ fn negamax_alpha_beta_cache(
         let mut best_value: i32 = i32::MIN + 1;
         let mut top_targets = TopTargets::new(6);
         for chess_move in valid_moves {
-            let child_score: Score = -negamax_alpha_beta_cache(
-                &board.make_move_new(chess_move),
-                stats,
-                remaining_depth - 1,
-                cache,
-                -beta,
-                -alpha,
-                calibration,
-            );
+            let child_score: Score =
+                -search_child(board, chess_move, ctx, remaining_depth - 1, -beta, -alpha);
+            if ctx.control.should_stop() {
+                return Score::Exact(0);
+            }
             let child_score_discounted = discount_checkmates(child_score.into());
             // Save if this is a good move
             top_targets.try_insert(child_score_discounted, &chess_move);
@@ -221,16 +681,143 @@ fn negamax_alpha_beta_cache(
             alpha = std::cmp::max(alpha, best_value);
             if best_value >= beta {
                 let score = Score::LowerBound(best_value);
-                insert_in_cache_if_better(board, remaining_depth, &score, top_targets, cache);
+                insert_in_cache_if_better(board, remaining_depth, &score, top_targets, ctx.cache);
                 return score;
             }
         }
         let score = Score::Exact(best_value);
-        insert_in_cache_if_better(board, remaining_depth, &score, top_targets, cache);
+        insert_in_cache_if_better(board, remaining_depth, &score, top_targets, ctx.cache);
         score
     }
 }
 
+// The tablebases only know the result of a position right after a capture or pawn move. Later
+// on, they cannot tell whether a win comes before the fifty-move rule. A null move only resets the
+// clock for the repetition rules, so it is not probed either.
+fn probe_tablebase(board: &Board, ctx: &SearchContext) -> Option<i32> {
+    let tablebase = ctx.tablebase?;
+    match ctx.path.last() {
+        Some(entry) if entry.halfmove_clock == 0 && !entry.is_null => {
+            tablebase.probe_wdl(board, ctx.calibration, Some(ctx.root_side))
+        }
+        _ => None,
+    }
+}
+
+// Makes the move, and searches the resulting position with the path extended accordingly.
+fn search_child(
+    board: &Board,
+    chess_move: ChessMove,
+    ctx: &mut SearchContext,
+    remaining_depth: i32,
+    alpha: i32,
+    beta: i32,
+) -> Score {
+    let child = board.make_move_new(chess_move);
+    let halfmove_clock = match ctx.path.last() {
+        Some(_) if resets_halfmove_clock(board, chess_move) => 0,
+        Some(parent) => parent.halfmove_clock + 1,
+        None => 0,
+    };
+    ctx.path.push(PathEntry {
+        hash: child.get_hash(),
+        halfmove_clock,
+        is_null: false,
+    });
+    let parent_eval = ctx
+        .evals
+        .last()
+        .expect("The root is always on the search line");
+    let child_eval = parent_eval.make_move(board, chess_move, ctx.calibration);
+    ctx.evals.push(child_eval);
+    let score = negamax_alpha_beta_cache(&child, ctx, remaining_depth, alpha, beta);
+    ctx.evals.pop();
+    ctx.path.pop();
+    score
+}
+
+pub(super) fn resets_halfmove_clock(board: &Board, chess_move: ChessMove) -> bool {
+    board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+        || board.piece_on(chess_move.get_dest()).is_some()
+}
+
+// Checks whether the last position of the path is a draw by repetition or the fifty-move rule.
+// Inside the search, a single repetition is treated as a draw, since it can be repeated again.
+fn draw_by_rule(board: &Board, path: &[PathEntry]) -> Option<DrawKind> {
+    let current = path.last()?;
+    if current.halfmove_clock >= 100 && board.status() != BoardStatus::Checkmate {
+        return Some(DrawKind::FiftyMove);
+    }
+    // Only positions with the same player to move, since the last irreversible move, can repeat.
+    let reversible = std::cmp::min(current.halfmove_clock as usize, path.len() - 1);
+    path[path.len() - 1 - reversible..path.len() - 1]
+        .iter()
+        .rev()
+        .skip(1)
+        .step_by(2)
+        .any(|entry| entry.hash == current.hash)
+        .then_some(DrawKind::Repetition)
+}
+
+// Replays the game to find the positions played so far. If the game did not start from the
+// standard position, only the current position is known, and its halfmove clock is taken to be 0.
+fn game_history(game: &Game) -> Vec<PathEntry> {
+    let mut board = Board::default();
+    let mut history = vec![PathEntry {
+        hash: board.get_hash(),
+        halfmove_clock: 0,
+        is_null: false,
+    }];
+    for action in game.actions() {
+        if let Action::MakeMove(chess_move) = action {
+            let halfmove_clock = if resets_halfmove_clock(&board, *chess_move) {
+                0
+            } else {
+                history.last().unwrap().halfmove_clock + 1
+            };
+            board = board.make_move_new(*chess_move);
+            history.push(PathEntry {
+                hash: board.get_hash(),
+                halfmove_clock,
+                is_null: false,
+            });
+        }
+    }
+    if board.get_hash() != game.current_position().get_hash() {
+        return vec![PathEntry {
+            hash: game.current_position().get_hash(),
+            halfmove_clock: 0,
+            is_null: false,
+        }];
+    }
+    history
+}
+
+fn add_noise(score: Score, board: &Board, ctx: &SearchContext) -> Score {
+    // Checkmate scores are left alone, as they sit at the very edges of the i32 range.
+    const THRESHOLD: i32 = 100;
+    let noisy = |value: i32| {
+        if (i32::MIN + THRESHOLD..=i32::MAX - THRESHOLD).contains(&value) {
+            value + evaluation_noise(board, ctx.eval_noise, ctx.noise_seed)
+        } else {
+            value
+        }
+    };
+    match score {
+        Score::Exact(value) => Score::Exact(noisy(value)),
+        Score::UpperBound(value) => Score::UpperBound(noisy(value)),
+        Score::LowerBound(value) => Score::LowerBound(noisy(value)),
+    }
+}
+
+fn format_moves(moves: &[ChessMove]) -> String {
+    moves
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 fn discount_checkmates(score: i32) -> i32 {
     // If score is very close to the CHECKMATE scores, discount by 1 (towards 0).
     // This ensures shorter checkmate lines are preferred.
@@ -253,3 +840,106 @@ fn null_move_pruning(board: &Board, remaining_depth: i32) -> Option<Board> {
     board.null_move()
     // Currently, we just do it all the time, but it should not be done in the endgame.
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stockwishbot::evaluation::rule_score;
+    use std::str::FromStr;
+
+    fn calibration() -> Calibration {
+        Calibration {
+            contempt: 20,
+            repetition_draw: 5,
+            fifty_move_draw: -10,
+            insufficient_material_draw: 15,
+            ..Calibration::default()
+        }
+    }
+
+    // Plays the moves from the board, keeping the path the search would keep.
+    fn play(board: Board, halfmove_clock: u32, moves: &[&str]) -> (Board, Vec<PathEntry>) {
+        let mut board = board;
+        let mut path = vec![PathEntry {
+            hash: board.get_hash(),
+            halfmove_clock,
+            is_null: false,
+        }];
+        for uci in moves {
+            let chess_move = ChessMove::from_str(uci).unwrap();
+            let halfmove_clock = if resets_halfmove_clock(&board, chess_move) {
+                0
+            } else {
+                path.last().unwrap().halfmove_clock + 1
+            };
+            board = board.make_move_new(chess_move);
+            path.push(PathEntry {
+                hash: board.get_hash(),
+                halfmove_clock,
+                is_null: false,
+            });
+        }
+        (board, path)
+    }
+
+    // The root side sees (base - contempt), and its opponent the negation.
+    fn assert_scored_for_root(calibration: &Calibration, kind: DrawKind, base: i32) {
+        let for_root = (base - calibration.contempt) * CENTIPAWN;
+        for root_side in [Color::White, Color::Black] {
+            assert_eq!(
+                calibration.draw_score(kind, root_side, Some(root_side)),
+                for_root
+            );
+            assert_eq!(
+                calibration.draw_score(kind, !root_side, Some(root_side)),
+                -for_root
+            );
+        }
+        assert_eq!(calibration.draw_score(kind, Color::White, None), 0);
+    }
+
+    #[test]
+    fn repetitions_score_the_repetition_draw_minus_contempt() {
+        let calibration = calibration();
+        let (board, path) = play(Board::default(), 0, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        let kind = draw_by_rule(&board, &path).unwrap();
+        assert_eq!(kind, DrawKind::Repetition);
+        assert_scored_for_root(&calibration, kind, calibration.repetition_draw);
+
+        let (board, path) = play(Board::default(), 0, &["g1f3", "g8f6", "f3g1"]);
+        assert_eq!(draw_by_rule(&board, &path), None);
+    }
+
+    #[test]
+    fn fifty_moves_score_the_fifty_move_draw_minus_contempt() {
+        let calibration = calibration();
+        let start = Board::from_str("4k3/8/8/8/8/8/R7/4K3 w - - 0 1").unwrap();
+        let (board, path) = play(start, 99, &["a2b2"]);
+        let kind = draw_by_rule(&board, &path).unwrap();
+        assert_eq!(kind, DrawKind::FiftyMove);
+        assert_scored_for_root(&calibration, kind, calibration.fifty_move_draw);
+
+        let (board, path) = play(start, 98, &["a2b2"]);
+        assert_eq!(draw_by_rule(&board, &path), None);
+    }
+
+    #[test]
+    fn insufficient_material_scores_its_draw_minus_contempt() {
+        let calibration = calibration();
+        assert_scored_for_root(
+            &calibration,
+            DrawKind::InsufficientMaterial,
+            calibration.insufficient_material_draw,
+        );
+        let board = Board::from_str("4k3/8/8/8/8/8/8/3BK3 w - - 0 1").unwrap();
+        let for_root = (calibration.insufficient_material_draw - calibration.contempt) * CENTIPAWN;
+        assert_eq!(
+            rule_score(&board, &calibration, Some(Color::White)),
+            Some(for_root)
+        );
+        assert_eq!(
+            rule_score(&board, &calibration, Some(Color::Black)),
+            Some(-for_root)
+        );
+    }
+}
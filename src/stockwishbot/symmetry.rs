@@ -0,0 +1,75 @@
+// Checks that the evaluation treats both players the same. A position and its colour-flipped twin,
+// with the board mirrored between the first and eighth rank and the colours swapped, must score
+// the same for the player who's turn it is.
+use chess::Board;
+use chess::MoveGen;
+use std::str::FromStr;
+
+use super::evaluation::raw_board_score;
+use super::Calibration;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Asymmetry {
+    pub fen: String,
+    pub score: i32,
+    pub flipped_score: i32,
+}
+
+// The same position with the colours swapped.
+pub fn color_flipped(board: &Board) -> Board {
+    let fen = board.to_string();
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let placement = fields[0]
+        .split('/')
+        .rev()
+        .map(swap_case)
+        .collect::<Vec<String>>()
+        .join("/");
+    let side = if fields[1] == "w" { "b" } else { "w" };
+    let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+    // White's rights come first, kingside before queenside
+    castling.sort_by_key(|c| (c.is_ascii_lowercase(), *c != 'K' && *c != 'k'));
+    let castling: String = castling.into_iter().collect();
+    let en_passant = match fields[3] {
+        "-" => "-".to_string(),
+        square => square.replace('3', "x").replace('6', "3").replace('x', "6"),
+    };
+    let flipped = [placement.as_str(), side, &castling, &en_passant]
+        .into_iter()
+        .chain(fields[4..].iter().copied())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    Board::from_str(&flipped).expect("A flipped position is as legal as the original")
+}
+
+fn swap_case(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_uppercase() {
+                c.to_ascii_lowercase()
+            } else {
+                c.to_ascii_uppercase()
+            }
+        })
+        .collect()
+}
+
+// Compares every position, and every position one move further, against its colour-flipped twin.
+pub fn check_symmetry(boards: &[Board], calibration: &Calibration) -> Vec<Asymmetry> {
+    let mut asymmetries = vec![];
+    for board in boards {
+        let children = MoveGen::new_legal(board).map(|m| board.make_move_new(m));
+        for position in std::iter::once(*board).chain(children) {
+            let score = raw_board_score(&position, calibration);
+            let flipped_score = raw_board_score(&color_flipped(&position), calibration);
+            if score != flipped_score {
+                asymmetries.push(Asymmetry {
+                    fen: position.to_string(),
+                    score,
+                    flipped_score,
+                });
+            }
+        }
+    }
+    asymmetries
+}
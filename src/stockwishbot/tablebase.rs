@@ -0,0 +1,307 @@
+// Syzygy endgame tablebases, read from a local directory. The WDL tables tell the search whether a
+// position is won, drawn or lost. The DTZ tables tell the root which moves keep the best result,
+// and make progress towards it.
+use chess::Board;
+use chess::BoardStatus;
+use chess::CastleRights;
+use chess::ChessMove;
+use chess::Color;
+use chess::MoveGen;
+use chess::Piece;
+use shakmaty::CastlingMode;
+use shakmaty::Chess;
+use shakmaty::FromSetup;
+use shakmaty::Role;
+use shakmaty::Setup;
+use shakmaty_syzygy::Wdl;
+use std::path::Path;
+
+use super::evaluation::CENTIPAWN;
+use super::stockwish::resets_halfmove_clock;
+use super::stockwish::Calibration;
+use super::stockwish::DrawKind;
+
+// Above every heuristic evaluation, including the known endgames, but well below checkmate.
+const TABLEBASE_WIN: i32 = 20_000 * CENTIPAWN;
+// Root moves are ranked by what they lead to. Wins are better the sooner they reset the
+// fifty-move counter, and losses the later.
+const RANK_WIN: i32 = 1000;
+
+pub struct Tablebase {
+    tables: shakmaty_syzygy::Tablebase<Chess>,
+}
+
+impl Tablebase {
+    // Uses all tables found in the directory. Tables are only read once they are probed.
+    pub fn open(directory: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut tables = shakmaty_syzygy::Tablebase::new();
+        let count = tables.add_directory(&directory)?;
+        println!(
+            "Found {} tablebase files in {}, for up to {} pieces",
+            count,
+            directory.as_ref().display(),
+            tables.max_pieces()
+        );
+        Ok(Self { tables })
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.tables.max_pieces()
+    }
+
+    // Whether the board is small enough to probe. Positions with castling rights are not in the tables.
+    fn covers(&self, board: &Board) -> bool {
+        board.combined().popcnt() as usize <= self.max_pieces()
+            && board.castle_rights(Color::White) == CastleRights::NoRights
+            && board.castle_rights(Color::Black) == CastleRights::NoRights
+    }
+
+    // The score of a board right after a capture or pawn move, from the point-of-view of the player
+    // to move. Later in the fifty moves the WDL tables cannot tell whether a win is still in time.
+    // Draws are scored for the given root side, as by Calibration::draw_score.
+    pub fn probe_wdl(
+        &self,
+        board: &Board,
+        calibration: &Calibration,
+        root_side: Option<Color>,
+    ) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        let wdl = self
+            .tables
+            .probe_wdl_after_zeroing(&position(board, 0)?)
+            .ok()?;
+        Some(wdl_score(wdl, calibration, board.side_to_move(), root_side))
+    }
+
+    // The legal moves which keep the best result the tables know of, or None if the board is not
+    // in the tables. Of the winning moves, only those closest to resetting the fifty-move counter
+    // are kept, so the game makes progress.
+    pub fn root_moves(&self, board: &Board, halfmove_clock: u32) -> Option<Vec<ChessMove>> {
+        if !self.covers(board) {
+            return None;
+        }
+        let mut ranked = vec![];
+        for chess_move in MoveGen::new_legal(board) {
+            let child = board.make_move_new(chess_move);
+            let clock = if resets_halfmove_clock(board, chess_move) {
+                0
+            } else {
+                halfmove_clock + 1
+            };
+            ranked.push((self.rank(&child, clock)?, chess_move));
+        }
+        let best = ranked.iter().map(|(rank, _)| *rank).max()?;
+        Some(
+            ranked
+                .into_iter()
+                .filter(|(rank, _)| *rank == best)
+                .map(|(_, chess_move)| chess_move)
+                .collect(),
+        )
+    }
+
+    // How good the board is for the player who just moved into it
+    fn rank(&self, child: &Board, halfmove_clock: u32) -> Option<i32> {
+        match child.status() {
+            BoardStatus::Checkmate => return Some(RANK_WIN + 1),
+            BoardStatus::Stalemate => return Some(0),
+            BoardStatus::Ongoing => {}
+        }
+        // The DTZ of some positions is rounded by a ply, which only matters right at the limit.
+        let dtz = self
+            .tables
+            .probe_dtz(&position(child, halfmove_clock)?)
+            .ok()?
+            .ignore_rounding()
+            .0;
+        Some(dtz_rank(dtz, halfmove_clock))
+    }
+}
+
+// The score of a WDL result for the player to move
+fn wdl_score(
+    wdl: Wdl,
+    calibration: &Calibration,
+    side_to_move: Color,
+    root_side: Option<Color>,
+) -> i32 {
+    match wdl {
+        Wdl::Win => TABLEBASE_WIN,
+        Wdl::Loss => -TABLEBASE_WIN,
+        // Won or lost, but not before the fifty-move rule makes it a draw
+        Wdl::CursedWin | Wdl::BlessedLoss => {
+            calibration.draw_score(DrawKind::FiftyMove, side_to_move, root_side)
+        }
+        Wdl::Draw => calibration.draw_score(DrawKind::Tablebase, side_to_move, root_side),
+    }
+}
+
+// The rank of a move into a board with the given DTZ, which is from the point-of-view of the
+// opponent, and halfmove clock
+fn dtz_rank(dtz: i32, halfmove_clock: u32) -> i32 {
+    // Whether the counter is reset in time to avoid the fifty-move rule
+    let in_time = halfmove_clock as i32 + dtz.abs() <= 100;
+    match dtz {
+        // The opponent is lost
+        dtz if dtz < 0 && in_time => RANK_WIN + dtz,
+        dtz if dtz < 0 => 1,
+        0 => 0,
+        // The opponent wins
+        _ if in_time => -RANK_WIN + dtz,
+        _ => -1,
+    }
+}
+
+// The board with the given halfmove clock, as a position the tables understand. Boards with
+// castling rights are never probed, but their rights are set up all the same.
+fn position(board: &Board, halfmove_clock: u32) -> Option<Chess> {
+    let mut setup = Setup::empty();
+    for square in *board.combined() {
+        let piece = shakmaty::Piece {
+            color: color(board.color_on(square)?),
+            role: role(board.piece_on(square)?),
+        };
+        setup.board.set_piece_at(to_square(square), piece);
+    }
+    setup.turn = color(board.side_to_move());
+    // Castling rights are given by the squares of the rooks which may still castle
+    for (side, rank) in [
+        (Color::White, chess::Rank::First),
+        (Color::Black, chess::Rank::Eighth),
+    ] {
+        let rights = board.castle_rights(side);
+        if rights.has_kingside() {
+            let rook = chess::Square::make_square(rank, chess::File::H);
+            setup.castling_rights.add(to_square(rook));
+        }
+        if rights.has_queenside() {
+            let rook = chess::Square::make_square(rank, chess::File::A);
+            setup.castling_rights.add(to_square(rook));
+        }
+    }
+    // A chess::Board knows the pawn which may be taken en passant, and shakmaty the square behind it
+    setup.ep_square = board
+        .en_passant()
+        .and_then(|pawn| match board.side_to_move() {
+            Color::White => pawn.up(),
+            Color::Black => pawn.down(),
+        })
+        .map(to_square);
+    setup.halfmoves = halfmove_clock;
+    Chess::from_setup(setup, CastlingMode::Standard).ok()
+}
+
+fn to_square(square: chess::Square) -> shakmaty::Square {
+    shakmaty::Square::new(square.to_index() as u32)
+}
+
+fn color(color: Color) -> shakmaty::Color {
+    match color {
+        Color::White => shakmaty::Color::White,
+        Color::Black => shakmaty::Color::Black,
+    }
+}
+
+fn role(piece: Piece) -> Role {
+    match piece {
+        Piece::Pawn => Role::Pawn,
+        Piece::Knight => Role::Knight,
+        Piece::Bishop => Role::Bishop,
+        Piece::Rook => Role::Rook,
+        Piece::Queen => Role::Queen,
+        Piece::King => Role::King,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::EnPassantMode;
+    use shakmaty::Position;
+    use std::str::FromStr;
+
+    fn converted_fen(fen: &str, halfmove_clock: u32) -> String {
+        let board = Board::from_str(fen).unwrap();
+        let position = position(&board, halfmove_clock).unwrap();
+        assert_eq!(position.turn(), color(board.side_to_move()));
+        Fen::from_position(position, EnPassantMode::Legal).to_string()
+    }
+
+    #[test]
+    fn boards_convert_to_the_same_position() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 0 1",
+            "4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1",
+            "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1",
+            "8/8/8/4k3/4P3/4K3/8/8 b - - 0 1",
+        ] {
+            assert_eq!(converted_fen(fen, 0), fen);
+        }
+        assert_eq!(
+            converted_fen("4k3/8/8/8/8/8/8/4KR2 w - - 0 1", 37),
+            "4k3/8/8/8/8/8/8/4KR2 w - - 37 1"
+        );
+    }
+
+    #[test]
+    fn cursed_wins_and_blessed_losses_score_as_draws() {
+        let calibration = Calibration {
+            contempt: 10,
+            fifty_move_draw: 4,
+            ..Calibration::default()
+        };
+        let root_side = Some(Color::White);
+        for side_to_move in [Color::White, Color::Black] {
+            let sign = if side_to_move == Color::White { 1 } else { -1 };
+            let score = |wdl| wdl_score(wdl, &calibration, side_to_move, root_side);
+            assert_eq!(score(Wdl::Win), TABLEBASE_WIN);
+            assert_eq!(score(Wdl::Loss), -TABLEBASE_WIN);
+            assert_eq!(score(Wdl::CursedWin), sign * (4 - 10) * CENTIPAWN);
+            assert_eq!(score(Wdl::BlessedLoss), sign * (4 - 10) * CENTIPAWN);
+            assert_eq!(score(Wdl::Draw), sign * -10 * CENTIPAWN);
+        }
+    }
+
+    #[test]
+    fn root_moves_rank_by_distance_to_zeroing() {
+        // The sooner a win resets the counter the better, and the later a loss
+        assert!(dtz_rank(-3, 0) > dtz_rank(-5, 0));
+        assert!(dtz_rank(10, 0) > dtz_rank(2, 0));
+        // A win too late for the fifty-move rule is still better than a draw, but not than a win
+        assert_eq!(dtz_rank(-60, 50), 1);
+        assert!(dtz_rank(-60, 50) > dtz_rank(0, 50));
+        assert!(dtz_rank(-60, 50) < dtz_rank(-50, 50));
+        // And a loss too late is only worse than a draw
+        assert_eq!(dtz_rank(60, 50), -1);
+        assert!(dtz_rank(60, 50) > dtz_rank(50, 50));
+        assert!(dtz_rank(-99, 0) < RANK_WIN + 1);
+    }
+
+    #[test]
+    #[ignore = "needs the Syzygy tables for three pieces in the directory STOCKWISH_SYZYGY"]
+    fn local_tables_are_probed() {
+        let directory = std::env::var("STOCKWISH_SYZYGY").expect("STOCKWISH_SYZYGY is not set");
+        let tablebase = Tablebase::open(directory).unwrap();
+        let calibration = Calibration {
+            contempt: 10,
+            ..Calibration::default()
+        };
+        let probe = |board: &Board| tablebase.probe_wdl(board, &calibration, Some(Color::White));
+        let won = Board::from_str("7k/8/8/8/8/8/8/KQ6 w - - 0 1").unwrap();
+        assert_eq!(probe(&won), Some(TABLEBASE_WIN));
+        let lost = Board::from_str("7k/8/8/8/8/8/8/KQ6 b - - 0 1").unwrap();
+        assert_eq!(probe(&lost), Some(-TABLEBASE_WIN));
+        let drawn = Board::from_str("8/8/8/4k3/4P3/4K3/8/8 w - - 0 1").unwrap();
+        assert_eq!(probe(&drawn), Some(-10 * CENTIPAWN));
+        // Every move the root keeps must still win
+        let moves = tablebase.root_moves(&won, 0).unwrap();
+        assert!(!moves.is_empty());
+        for chess_move in moves {
+            assert_eq!(probe(&won.make_move_new(chess_move)), Some(-TABLEBASE_WIN));
+        }
+    }
+}
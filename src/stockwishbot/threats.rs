@@ -0,0 +1,132 @@
+// Threats against the pieces of both players. The quiescence search only finds a piece
+// en prise after a capture has been considered, so these terms let the static evaluation see it coming.
+use chess::between;
+use chess::get_bishop_rays;
+use chess::get_rook_rays;
+use chess::BitBoard;
+use chess::Board;
+use chess::Color;
+use chess::Piece;
+use chess::Square;
+use chess::ALL_COLORS;
+use chess::EMPTY;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::attacks::attacks_by_color;
+use super::attacks::attacks_by_piece;
+use super::evaluation::TaperedScore;
+use super::pawn_structure::pawn_attacks;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThreatWeights {
+    // Indexed by Piece::to_index() of the attacked piece
+    pub attacked_by_lower: [TaperedScore; 6],
+    // An attacked piece which is not defended at all
+    pub hanging: TaperedScore,
+    // A safe pawn push which attacks a piece
+    pub pawn_push_threat: TaperedScore,
+    pub pin_against_king: TaperedScore,
+    pub pin_against_queen: TaperedScore,
+}
+
+impl Default for ThreatWeights {
+    fn default() -> Self {
+        Self {
+            attacked_by_lower: [
+                TaperedScore::new(0, 0),
+                TaperedScore::new(35, 25),
+                TaperedScore::new(35, 25),
+                TaperedScore::new(40, 30),
+                TaperedScore::new(50, 40),
+                TaperedScore::new(0, 0),
+            ],
+            hanging: TaperedScore::new(20, 15),
+            pawn_push_threat: TaperedScore::new(15, 10),
+            pin_against_king: TaperedScore::new(15, 10),
+            pin_against_queen: TaperedScore::new(10, 5),
+        }
+    }
+}
+
+// Pieces of the same class do not threaten each other. Indexed by Piece::to_index().
+const PIECE_CLASS: [usize; 6] = [0, 1, 1, 2, 3, 4];
+
+// Indexed by Color::to_index(), each from that player's point-of-view, for the threats made by that player.
+pub fn threats(board: &Board, weights: &ThreatWeights) -> [TaperedScore; 2] {
+    ALL_COLORS.map(|color| threats_by_color(board, weights, color))
+}
+
+fn threats_by_color(board: &Board, weights: &ThreatWeights, color: Color) -> TaperedScore {
+    let enemy = *board.color_combined(!color);
+    let enemy_pieces = enemy & !board.pieces(Piece::Pawn) & !board.pieces(Piece::King);
+    let our_attacks = attacks_by_color(board, color);
+    let their_attacks = attacks_by_color(board, !color);
+    let mut score = TaperedScore::default();
+
+    // Pieces attacked by less valuable pieces
+    for victim in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let lower_attacks = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook]
+            .into_iter()
+            .filter(|attacker| PIECE_CLASS[attacker.to_index()] < PIECE_CLASS[victim.to_index()])
+            .fold(EMPTY, |attacks, attacker| {
+                attacks | attacks_by_piece(board, attacker, color)
+            });
+        let victims = board.pieces(victim) & enemy & lower_attacks;
+        score += weights.attacked_by_lower[victim.to_index()] * victims.popcnt() as i32;
+    }
+
+    // Undefended pieces and pawns under attack
+    let hanging = enemy & !board.pieces(Piece::King) & our_attacks & !their_attacks;
+    score += weights.hanging * hanging.popcnt() as i32;
+
+    // Pawn pushes to squares where the pawn is safe, and attacks a piece
+    let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(color);
+    let enemy_pawns = board.pieces(Piece::Pawn) & enemy;
+    let pushed = match color {
+        Color::White => BitBoard(own_pawns.0 << 8),
+        Color::Black => BitBoard(own_pawns.0 >> 8),
+    } & !board.combined();
+    let safe_pushes = pushed & !pawn_attacks(enemy_pawns, !color);
+    let push_victims = pawn_attacks(safe_pushes, color) & enemy_pieces;
+    score += weights.pawn_push_threat * push_victims.popcnt() as i32;
+
+    // Enemy pieces pinned against their king or queen by our sliders
+    score += pins(board, weights, color);
+    score
+}
+
+fn pins(board: &Board, weights: &ThreatWeights, color: Color) -> TaperedScore {
+    let own = *board.color_combined(color);
+    let enemy = *board.color_combined(!color);
+    let occupied = *board.combined();
+    let king_targets = board.pieces(Piece::King) & enemy;
+    let queen_targets = board.pieces(Piece::Queen) & enemy;
+    let diagonal = (board.pieces(Piece::Bishop) | board.pieces(Piece::Queen)) & own;
+    let straight = (board.pieces(Piece::Rook) | board.pieces(Piece::Queen)) & own;
+    let mut score = TaperedScore::default();
+    for (sliders, rays) in [
+        (diagonal, get_bishop_rays as fn(Square) -> BitBoard),
+        (straight, get_rook_rays as fn(Square) -> BitBoard),
+    ] {
+        for slider in sliders {
+            let is_queen = board.piece_on(slider) == Some(Piece::Queen);
+            for target in rays(slider) & (king_targets | queen_targets) {
+                // A queen pinning a piece to the other queen gains nothing
+                let against_king = king_targets & BitBoard::from_square(target) != EMPTY;
+                if !against_king && is_queen {
+                    continue;
+                }
+                let blockers = between(slider, target) & occupied;
+                if blockers.popcnt() == 1 && blockers & enemy != EMPTY {
+                    score += if against_king {
+                        weights.pin_against_king
+                    } else {
+                        weights.pin_against_queen
+                    };
+                }
+            }
+        }
+    }
+    score
+}
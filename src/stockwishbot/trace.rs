@@ -0,0 +1,134 @@
+// A breakdown of the evaluation of a single position, to see why the engine likes or dislikes it.
+// It is filled in by evaluate_with_trace, which is the very code raw_board_score runs.
+use chess::Piece;
+use chess::ALL_PIECES;
+use std::fmt;
+
+use super::evaluation::TaperedScore;
+use super::evaluation::CENTIPAWN;
+use super::evaluation::PHASE_MIDGAME;
+use super::material::SCALE_NORMAL;
+
+#[derive(Clone, Debug, Default)]
+pub struct EvalTrace {
+    // The terms are indexed by Color::to_index(), each from that player's point-of-view,
+    // in evaluation units with the scales of the weights applied.
+    pub material: [TaperedScore; 2],
+    pub imbalance: [TaperedScore; 2],
+    // Further indexed by Piece::to_index()
+    pub piece_square: [[TaperedScore; 6]; 2],
+    pub pawn_structure: [TaperedScore; 2],
+    pub mobility: [TaperedScore; 2],
+    pub king_safety: [TaperedScore; 2],
+    pub pieces: [TaperedScore; 2],
+    pub threats: [TaperedScore; 2],
+    // From 0 to PHASE_MIDGAME
+    pub phase: i32,
+    // How much of the endgame score is kept, out of SCALE_NORMAL
+    pub endgame_scale: i32,
+    // Set when the terms were skipped, e.g. for a checkmate or a known endgame
+    pub shortcut: Option<&'static str>,
+    // From the point-of-view of the player who's turn it is, exactly as returned by raw_board_score
+    pub score: i32,
+}
+
+impl EvalTrace {
+    // Every term with its name, in the order they are printed
+    pub fn terms(&self) -> Vec<(String, [TaperedScore; 2])> {
+        let mut terms = vec![
+            ("Material".to_string(), self.material),
+            ("Imbalance".to_string(), self.imbalance),
+        ];
+        for piece in ALL_PIECES {
+            let i = piece.to_index();
+            terms.push((
+                format!("PST {}", piece_name(piece)),
+                [self.piece_square[0][i], self.piece_square[1][i]],
+            ));
+        }
+        terms.extend([
+            ("Pawn structure".to_string(), self.pawn_structure),
+            ("Mobility".to_string(), self.mobility),
+            ("King safety".to_string(), self.king_safety),
+            ("Pieces".to_string(), self.pieces),
+            ("Threats".to_string(), self.threats),
+        ]);
+        terms
+    }
+
+    // White's total, before the endgame scale and the phase are applied
+    pub fn total(&self) -> TaperedScore {
+        // Not using terms(), since this runs for every evaluated position.
+        let piece_square = |color: usize| {
+            self.piece_square[color]
+                .iter()
+                .fold(TaperedScore::default(), |sum, &score| sum + score)
+        };
+        let sum = |color: usize| {
+            self.material[color]
+                + self.imbalance[color]
+                + piece_square(color)
+                + self.pawn_structure[color]
+                + self.mobility[color]
+                + self.king_safety[color]
+                + self.pieces[color]
+                + self.threats[color]
+        };
+        sum(0) - sum(1)
+    }
+}
+
+fn piece_name(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "pawns",
+        Piece::Knight => "knights",
+        Piece::Bishop => "bishops",
+        Piece::Rook => "rooks",
+        Piece::Queen => "queens",
+        Piece::King => "king",
+    }
+}
+
+// In pawns, with two decimals
+fn pawns(score: i32) -> String {
+    format!("{:.2}", score as f64 / (100 * CENTIPAWN) as f64)
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(shortcut) = self.shortcut {
+            writeln!(f, "Evaluated as {}", shortcut)?;
+            return writeln!(f, "Score (side to move): {}", pawns(self.score));
+        }
+        writeln!(
+            f,
+            "{:<16}{:>9}{:>9}{:>9}{:>9}{:>9}{:>9}",
+            "Term", "White mg", "White eg", "Black mg", "Black eg", "Total mg", "Total eg"
+        )?;
+        for (name, [white, black]) in self.terms() {
+            let total = white - black;
+            writeln!(
+                f,
+                "{:<16}{:>9}{:>9}{:>9}{:>9}{:>9}{:>9}",
+                name,
+                pawns(white.midgame),
+                pawns(white.endgame),
+                pawns(black.midgame),
+                pawns(black.endgame),
+                pawns(total.midgame),
+                pawns(total.endgame),
+            )?;
+        }
+        let total = self.total();
+        writeln!(
+            f,
+            "{:<52}{:>9}{:>9}",
+            "Total (white)",
+            pawns(total.midgame),
+            pawns(total.endgame)
+        )?;
+        writeln!(f, "Phase: {}/{}", self.phase, PHASE_MIDGAME)?;
+        writeln!(f, "Endgame scale: {}/{}", self.endgame_scale, SCALE_NORMAL)?;
+        writeln!(f, "Score (side to move): {}", pawns(self.score))
+    }
+}
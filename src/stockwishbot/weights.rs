@@ -0,0 +1,274 @@
+// All parameters of the evaluation, so they can be changed at runtime and compared against each other
+// without recompiling. They are stored as JSON.
+use chess::Piece;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use super::evaluation::MobilityWeights;
+use super::evaluation::PieceWeights;
+use super::evaluation::CENTIPAWN;
+use super::king_safety::KingSafetyWeights;
+use super::material::ImbalanceWeights;
+use super::material::ScaleWeights;
+use super::pawn_structure::PawnWeights;
+use super::threats::ThreatWeights;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EvalWeights {
+    // Material is multiplied by piece_value_scale, and everything else by positional_scale.
+    pub piece_value_scale: i32,
+    pub positional_scale: i32,
+    pub piece_values: PieceValues,
+    pub midgame_tables: PieceSquareTables,
+    pub endgame_tables: PieceSquareTables,
+    pub mobility: MobilityWeights,
+    pub pieces: PieceWeights,
+    pub pawns: PawnWeights,
+    pub king_safety: KingSafetyWeights,
+    pub threats: ThreatWeights,
+    pub imbalance: ImbalanceWeights,
+    pub scaling: ScaleWeights,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self {
+            piece_value_scale: CENTIPAWN,
+            positional_scale: 1,
+            piece_values: PieceValues::default(),
+            midgame_tables: PieceSquareTables {
+                pawn: PAWN_MIDGAME,
+                knight: KNIGHT_MIDGAME,
+                bishop: BISHOP_MIDGAME,
+                rook: ROOK_MIDGAME,
+                queen: QUEEN_MIDGAME,
+                king: KING_MIDGAME,
+            },
+            endgame_tables: PieceSquareTables {
+                pawn: PAWN_ENDGAME,
+                knight: KNIGHT_ENDGAME,
+                bishop: BISHOP_ENDGAME,
+                rook: ROOK_ENDGAME,
+                queen: QUEEN_ENDGAME,
+                king: KING_ENDGAME,
+            },
+            mobility: MobilityWeights::default(),
+            pieces: PieceWeights::default(),
+            pawns: PawnWeights::default(),
+            king_safety: KingSafetyWeights::default(),
+            threats: ThreatWeights::default(),
+            imbalance: ImbalanceWeights::default(),
+            scaling: ScaleWeights::default(),
+        }
+    }
+}
+
+impl EvalWeights {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// In centipawns
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceValues {
+    pub pawn: i32,
+    pub knight: i32,
+    pub bishop: i32,
+    pub rook: i32,
+    pub queen: i32,
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        Self {
+            pawn: 100,
+            knight: 320,
+            bishop: 330,
+            rook: 500,
+            queen: 900,
+        }
+    }
+}
+
+impl PieceValues {
+    pub fn of(&self, piece: Piece) -> i32 {
+        match piece {
+            Piece::Pawn => self.pawn,
+            Piece::Knight => self.knight,
+            Piece::Bishop => self.bishop,
+            Piece::Rook => self.rook,
+            Piece::Queen => self.queen,
+            Piece::King => 0,
+        }
+    }
+
+    // The non-pawn material of both players at the start of the game, where the phase is highest
+    pub fn starting_non_pawn_material(&self) -> i32 {
+        4 * self.knight + 4 * self.bishop + 4 * self.rook + 2 * self.queen
+    }
+}
+
+// Positional values for white's pieces, in the same units as the other positional terms. Each table is
+// laid out as seen from white's side of the board, so the first row is the eighth rank.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceSquareTables {
+    pub pawn: [[i32; 8]; 8],
+    pub knight: [[i32; 8]; 8],
+    pub bishop: [[i32; 8]; 8],
+    pub rook: [[i32; 8]; 8],
+    pub queen: [[i32; 8]; 8],
+    pub king: [[i32; 8]; 8],
+}
+
+impl PieceSquareTables {
+    pub fn of(&self, piece: Piece) -> &[[i32; 8]; 8] {
+        match piece {
+            Piece::Pawn => &self.pawn,
+            Piece::Knight => &self.knight,
+            Piece::Bishop => &self.bishop,
+            Piece::Rook => &self.rook,
+            Piece::Queen => &self.queen,
+            Piece::King => &self.king,
+        }
+    }
+}
+
+//
+// The default piece-square tables
+//
+
+// Pawns
+const PAWN_MIDGAME: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [10, 10, 20, 30, 30, 20, 10, 10],
+    [5, 5, 10, 25, 25, 10, 5, 5],
+    [0, 0, 0, 20, 20, 0, 0, 0],
+    [5, -5, -10, 0, 0, -10, -5, 5],
+    [5, 10, 10, -20, -20, 10, 10, 5],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+const PAWN_ENDGAME: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [80, 80, 80, 80, 80, 80, 80, 80],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [30, 30, 30, 30, 30, 30, 30, 30],
+    [15, 15, 15, 15, 15, 15, 15, 15],
+    [5, 5, 5, 5, 5, 5, 5, 5],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+// Knights
+const KNIGHT_MIDGAME: [[i32; 8]; 8] = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20, 0, 0, 0, 0, -20, -40],
+    [-30, 0, 10, 15, 15, 10, 0, -30],
+    [-30, 5, 15, 20, 20, 15, 5, -30],
+    [-30, 0, 15, 20, 20, 15, 0, -30],
+    [-30, 5, 10, 15, 15, 10, 5, -30],
+    [-40, -20, 0, 5, 5, 0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+const KNIGHT_ENDGAME: [[i32; 8]; 8] = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20, -10, -5, -5, -10, -20, -40],
+    [-30, -10, 5, 10, 10, 5, -10, -30],
+    [-30, -5, 10, 15, 15, 10, -5, -30],
+    [-30, -5, 10, 15, 15, 10, -5, -30],
+    [-30, -10, 5, 10, 10, 5, -10, -30],
+    [-40, -20, -10, -5, -5, -10, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+// Bishops
+const BISHOP_MIDGAME: [[i32; 8]; 8] = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 10, 10, 5, 0, -10],
+    [-10, 5, 5, 10, 10, 5, 5, -10],
+    [-10, 0, 10, 10, 10, 10, 0, -10],
+    [-10, 10, 10, 10, 10, 10, 10, -10],
+    [-10, 5, 0, 0, 0, 0, 5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+const BISHOP_ENDGAME: [[i32; 8]; 8] = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-10, 0, 5, 10, 10, 5, 0, -10],
+    [-10, 0, 5, 10, 10, 5, 0, -10],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+// Rooks
+const ROOK_MIDGAME: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [5, 10, 10, 10, 10, 10, 10, 5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [0, 0, 0, 5, 5, 0, 0, 0],
+];
+const ROOK_ENDGAME: [[i32; 8]; 8] = [
+    [5, 5, 5, 5, 5, 5, 5, 5],
+    [10, 10, 10, 10, 10, 10, 10, 10],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+// Queens
+const QUEEN_MIDGAME: [[i32; 8]; 8] = [
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-5, 0, 5, 5, 5, 5, 0, -5],
+    [0, 0, 5, 5, 5, 5, 0, -5],
+    [-10, 5, 5, 5, 5, 5, 0, -10],
+    [-10, 0, 5, 0, 0, 0, 0, -10],
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+];
+const QUEEN_ENDGAME: [[i32; 8]; 8] = [
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-10, 5, 10, 10, 10, 10, 5, -10],
+    [-5, 5, 10, 15, 15, 10, 5, -5],
+    [-5, 5, 10, 15, 15, 10, 5, -5],
+    [-10, 5, 10, 10, 10, 10, 5, -10],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+];
+// Kings
+const KING_MIDGAME: [[i32; 8]; 8] = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [20, 20, 0, 0, 0, 0, 20, 20],
+    [20, 30, 10, 0, 0, 10, 30, 20],
+];
+const KING_ENDGAME: [[i32; 8]; 8] = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10, 0, 0, -10, -20, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -30, 0, 0, 0, 0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
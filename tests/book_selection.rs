@@ -0,0 +1,58 @@
+use chess::Board;
+use chess::ChessMove;
+use chess::Game;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use stockwish::stockwishbot::encode_move;
+use stockwish::stockwishbot::polyglot_key;
+use stockwish::stockwishbot::BookEntry;
+use stockwish::stockwishbot::BookOptions;
+use stockwish::stockwishbot::BookSelection;
+use stockwish::stockwishbot::Calibration;
+use stockwish::stockwishbot::OpeningBook;
+use stockwish::stockwishbot::StockWish;
+
+// A book with several equally weighted first moves
+fn first_move_book() -> OpeningBook {
+    let board = Board::default();
+    let entries = ["e2e4", "d2d4", "c2c4", "g1f3"]
+        .iter()
+        .map(|uci| BookEntry {
+            key: polyglot_key(&board),
+            raw_move: encode_move(&board, ChessMove::from_str(uci).unwrap()),
+            weight: 1,
+            learn: 0,
+        })
+        .collect();
+    OpeningBook::from_entries(entries)
+}
+
+fn book_engine(selection: BookSelection) -> StockWish {
+    let mut stockwish = StockWish::new(2, Calibration::default());
+    let options = BookOptions {
+        selection,
+        seed: 7,
+        ..BookOptions::default()
+    };
+    stockwish.set_book(Some(Arc::new(first_move_book())), options);
+    stockwish
+}
+
+fn book_move(stockwish: &mut StockWish) -> ChessMove {
+    stockwish.analyse_iterative_deepening(Game::new())[0].pv[0]
+}
+
+#[test]
+fn successive_searches_advance_the_book_generator() {
+    for selection in [BookSelection::Weighted, BookSelection::Random] {
+        // A fresh engine with the same seed always makes the same first choice
+        let first = book_move(&mut book_engine(selection));
+        assert!((0..4).all(|_| book_move(&mut book_engine(selection)) == first));
+        // The same engine goes on to the next random choice
+        let mut stockwish = book_engine(selection);
+        let moves: HashSet<ChessMove> = (0..16).map(|_| book_move(&mut stockwish)).collect();
+        assert!(moves.len() > 1, "{:?} always chose {}", selection, first);
+    }
+}
@@ -0,0 +1,52 @@
+use chess::Board;
+use std::str::FromStr;
+
+use stockwish::stockwishbot::check_symmetry;
+use stockwish::stockwishbot::color_flipped;
+use stockwish::stockwishbot::Calibration;
+
+// Openings, middlegames and endgames, with castling rights and en passant squares on both sides
+const CORPUS: [&str; 16] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "2kr3r/ppp2ppp/2n5/2b1p3/4P1q1/2NP4/PPPQ1PPP/R3KB1R b KQ - 3 12",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 40",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/8/8/3K4/4P3/8 w - - 0 60",
+    "8/8/8/3k4/8/8/8/KQ6 b - - 0 70",
+    "8/8/8/4k3/8/8/8/KBN5 w - - 0 70",
+    "4k3/8/8/2b5/8/8/3B1PPP/6K1 w - - 0 50",
+    "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 30",
+    "8/5pk1/6p1/8/8/6P1/5PK1/5B2 b - - 0 45",
+];
+
+fn corpus() -> Vec<Board> {
+    CORPUS
+        .iter()
+        .map(|fen| Board::from_str(fen).expect("Invalid FEN in the corpus"))
+        .collect()
+}
+
+#[test]
+fn flipping_twice_gives_the_same_position() {
+    for board in corpus() {
+        assert_eq!(color_flipped(&color_flipped(&board)), board);
+    }
+}
+
+#[test]
+fn evaluation_is_colour_symmetric() {
+    let asymmetries = check_symmetry(&corpus(), &Calibration::default());
+    for asymmetry in &asymmetries {
+        println!(
+            "{}: {} but {} when flipped",
+            asymmetry.fen, asymmetry.score, asymmetry.flipped_score
+        );
+    }
+    assert!(asymmetries.is_empty());
+}
@@ -0,0 +1,47 @@
+use chess::Board;
+use chess::ChessMove;
+use chess::Game;
+use chess::MoveGen;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use stockwish::stockwishbot::AnalysisLine;
+use stockwish::stockwishbot::Calibration;
+use stockwish::stockwishbot::StockWish;
+
+fn analyse(fen: &str, multi_pv: usize) -> Vec<AnalysisLine> {
+    let mut stockwish = StockWish::new(4, Calibration::default());
+    stockwish.set_multi_pv(multi_pv);
+    stockwish.analyse_iterative_deepening(Game::from_str(fen).unwrap())
+}
+
+// Every line has its own root move, starts its PV with it, and the lines are ranked by score.
+fn assert_distinct_and_ranked(lines: &[AnalysisLine]) {
+    let root_moves: HashSet<ChessMove> = lines.iter().map(|line| line.pv[0]).collect();
+    assert_eq!(root_moves.len(), lines.len());
+    for pair in lines.windows(2) {
+        assert!(pair[0].score >= pair[1].score);
+    }
+}
+
+#[test]
+fn lines_are_distinct_and_ranked() {
+    // Only the rook can take the undefended queen
+    let fen = "4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1";
+    let lines = analyse(fen, 3);
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|line| !line.pv.is_empty()));
+    assert_distinct_and_ranked(&lines);
+    assert_eq!(lines[0].pv[0], ChessMove::from_str("d1d5").unwrap());
+    assert!(lines[0].score > lines[1].score);
+}
+
+#[test]
+fn more_lines_than_legal_moves_returns_every_move() {
+    let fen = "k7/8/8/8/8/8/PP6/K7 w - - 0 1";
+    let legal_moves = MoveGen::new_legal(&Board::from_str(fen).unwrap()).len();
+    let lines = analyse(fen, legal_moves + 3);
+    assert_eq!(lines.len(), legal_moves);
+    assert!(lines.iter().all(|line| !line.pv.is_empty()));
+    assert_distinct_and_ranked(&lines);
+}
@@ -0,0 +1,122 @@
+use chess::Board;
+use chess::ChessMove;
+use chess::MoveGen;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use stockwish::stockwishbot::Calibration;
+use stockwish::stockwishbot::EvaluationMode;
+use stockwish::stockwishbot::IncrementalEval;
+use stockwish::stockwishbot::Network;
+
+const HIDDEN: usize = 16;
+
+// A network file with small pseudo-random weights, in the format described in nnue.rs
+fn network_bytes() -> Vec<u8> {
+    let mut seed: u32 = 12345;
+    let mut next = || {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        ((seed >> 16) % 64) as i16 - 32
+    };
+    let mut bytes = b"SWNN".to_vec();
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend((HIDDEN as u32).to_le_bytes());
+    for _ in 0..(768 * HIDDEN + HIDDEN + 2 * HIDDEN) {
+        bytes.extend(next().to_le_bytes());
+    }
+    bytes.extend(100i32.to_le_bytes());
+    bytes
+}
+
+fn network_calibration() -> (Arc<Network>, Calibration) {
+    let network = Arc::new(Network::from_bytes(&network_bytes()).expect("Invalid network"));
+    let calibration = Calibration {
+        evaluation: EvaluationMode::Network(network.clone()),
+        ..Calibration::default()
+    };
+    (network, calibration)
+}
+
+// Plays the moves from the position, checking the incremental state against a fresh one after each
+fn check_incremental(fen: &str, moves: &[&str]) {
+    let (network, calibration) = network_calibration();
+    let mut board = Board::from_str(fen).expect("Invalid FEN");
+    let mut state = IncrementalEval::new(&board, &calibration);
+    for uci in moves {
+        let chess_move = ChessMove::from_str(uci).expect("Invalid move");
+        assert!(board.legal(chess_move), "{} is not legal in {}", uci, board);
+        state = state.make_move(&board, chess_move, &calibration);
+        board = board.make_move_new(chess_move);
+        assert_eq!(
+            state.accumulator,
+            Some(network.accumulator(&board)),
+            "after {}",
+            uci
+        );
+        assert_eq!(
+            state,
+            IncrementalEval::new(&board, &calibration),
+            "after {}",
+            uci
+        );
+    }
+}
+
+#[test]
+fn network_bytes_round_trip() {
+    let bytes = network_bytes();
+    let network = Network::from_bytes(&bytes).expect("Invalid network");
+    assert_eq!(network.hidden_size(), HIDDEN);
+    assert_eq!(network.to_bytes(), bytes);
+    assert_eq!(Network::from_bytes(&network.to_bytes()).unwrap(), network);
+}
+
+#[test]
+fn truncated_network_is_rejected() {
+    let bytes = network_bytes();
+    assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn incremental_accumulator_after_captures() {
+    check_incremental(
+        "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+        &["e4d5", "d8d5", "b1c3", "d5d2", "c1d2"],
+    );
+}
+
+#[test]
+fn incremental_accumulator_after_en_passant() {
+    check_incremental("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", &["d5e6"]);
+    check_incremental("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1", &["d4e3"]);
+}
+
+#[test]
+fn incremental_accumulator_after_castling() {
+    let fen = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+    check_incremental(fen, &["e1g1", "e8c8"]);
+    check_incremental(fen, &["e1c1", "e8g8"]);
+}
+
+#[test]
+fn incremental_accumulator_after_promotions() {
+    check_incremental("1n2k3/P7/8/8/8/8/7p/4K3 w - - 0 1", &["a7a8q", "h2h1n"]);
+    check_incremental("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1", &["a7b8r"]);
+}
+
+#[test]
+fn incremental_accumulator_through_a_game() {
+    let (network, calibration) = network_calibration();
+    let mut board = Board::default();
+    let mut state = IncrementalEval::new(&board, &calibration);
+    for ply in 0..60 {
+        let moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+        if moves.is_empty() {
+            break;
+        }
+        let chess_move = moves[(ply * 7) % moves.len()];
+        state = state.make_move(&board, chess_move, &calibration);
+        board = board.make_move_new(chess_move);
+        assert_eq!(state.accumulator, Some(network.accumulator(&board)));
+    }
+}
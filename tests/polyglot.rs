@@ -0,0 +1,67 @@
+use chess::Board;
+use chess::ChessMove;
+use std::str::FromStr;
+
+use stockwish::stockwishbot::decode_move;
+use stockwish::stockwishbot::encode_move;
+use stockwish::stockwishbot::polyglot_key;
+
+// The board after the moves from the starting position
+fn play(moves: &[&str]) -> Board {
+    moves.iter().fold(Board::default(), |board, uci| {
+        let chess_move = ChessMove::from_str(uci).expect("Invalid move");
+        assert!(board.legal(chess_move), "{} is not legal in {}", uci, board);
+        board.make_move_new(chess_move)
+    })
+}
+
+// The test positions of the Polyglot book format specification
+#[test]
+fn polyglot_keys_match_the_specification() {
+    for (moves, key) in [
+        (vec![], 0x463b96181691fc9c),
+        (vec!["e2e4"], 0x823c9b50fd114196),
+        (vec!["e2e4", "d7d5"], 0x0756b94461c50fb0),
+        (vec!["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+        (vec!["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78),
+        (
+            vec!["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"],
+            0x652a607ca3f242c1,
+        ),
+        (
+            vec!["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"],
+            0x00fdd303c946bdd9,
+        ),
+        (
+            vec!["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"],
+            0x3c8123ea7b067637,
+        ),
+        (
+            vec!["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"],
+            0x5c3f9b829b279560,
+        ),
+    ] {
+        assert_eq!(polyglot_key(&play(&moves)), key, "after {:?}", moves);
+    }
+}
+
+// Polyglot writes castling as the king taking its own rook
+#[test]
+fn castling_moves_are_decoded_and_encoded() {
+    let board = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let black = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+    // The from square times 64 plus the to square, with a1 as 0
+    for (board, raw_move, uci) in [
+        (board, 4 * 64 + 7, "e1g1"),
+        (board, 4 * 64, "e1c1"),
+        (black, 60 * 64 + 63, "e8g8"),
+        (black, 60 * 64 + 56, "e8c8"),
+    ] {
+        let chess_move = ChessMove::from_str(uci).unwrap();
+        assert_eq!(decode_move(&board, raw_move), Some(chess_move));
+        assert_eq!(encode_move(&board, chess_move), raw_move);
+    }
+    // Without the right to castle, the king cannot take its rook
+    let no_rights = Board::from_str("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+    assert_eq!(decode_move(&no_rights, 4 * 64 + 7), None);
+}
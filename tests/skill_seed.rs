@@ -0,0 +1,69 @@
+use chess::ChessMove;
+use chess::Game;
+use std::collections::HashSet;
+
+use stockwish::stockwishbot::AnalysisLine;
+use stockwish::stockwishbot::Calibration;
+use stockwish::stockwishbot::Skill;
+use stockwish::stockwishbot::StockWish;
+use stockwish::stockwishbot::CENTIPAWN;
+use stockwish::stockwishbot::MAX_SKILL_LEVEL;
+
+// Only the evaluation noise is random, so any difference between seeds comes from the noise.
+fn noisy_move(seed: u64) -> Option<ChessMove> {
+    let mut stockwish = StockWish::new(2, Calibration::default());
+    stockwish.set_skill(Skill {
+        eval_noise: 50,
+        ..Skill::from_level(MAX_SKILL_LEVEL).with_seed(seed)
+    });
+    stockwish.best_next_move_iterative_deepening(Game::new())
+}
+
+#[test]
+fn the_same_seed_plays_the_same_move() {
+    for seed in 0..4 {
+        assert_eq!(noisy_move(seed), noisy_move(seed));
+    }
+}
+
+#[test]
+fn different_seeds_can_play_different_moves() {
+    let moves: HashSet<ChessMove> = (0..16).filter_map(noisy_move).collect();
+    assert!(moves.len() > 1);
+}
+
+#[test]
+fn selection_stays_within_the_margin() {
+    let mut stockwish = StockWish::new(2, Calibration::default());
+    let skill = Skill::from_level(0).with_seed(3);
+    stockwish.set_skill(skill);
+    let margin = skill.selection_margin * CENTIPAWN;
+    let line = |score: i32| AnalysisLine {
+        score,
+        depth: 1,
+        pv: vec![],
+    };
+    let lines = [
+        line(0),
+        line(-margin / 2),
+        line(-margin),
+        line(-margin - 1),
+        line(-3 * margin),
+    ];
+    let scores: HashSet<i32> = (0..200)
+        .map(|_| stockwish.pick_line(&lines).unwrap().score)
+        .collect();
+    assert!(scores.iter().all(|&score| score >= -margin));
+    assert!(scores.len() > 1);
+}
+
+#[test]
+fn full_strength_lifts_the_node_limit() {
+    let mut stockwish = StockWish::new(4, Calibration::default());
+    stockwish.set_skill(Skill::from_level(0).with_seed(0));
+    stockwish.analyse_iterative_deepening(Game::new());
+    stockwish.set_skill(Skill::from_level(MAX_SKILL_LEVEL));
+    let lines = stockwish.analyse_iterative_deepening(Game::new());
+    // Depths run up to one less than the depth given to StockWish::new
+    assert_eq!(lines[0].depth, 3);
+}